@@ -0,0 +1,40 @@
+//! Benchmarks every registered variant of each day and part against the same
+//! input, after checking that they all agree on the answer.
+//!
+//! Inputs are read from the local store in `input/2021`; days without an input
+//! are skipped.
+use aoc_2021::{solutions, store::Store};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn variants(c: &mut Criterion) {
+    let store = Store::default();
+    for (day, part) in solutions::day_parts() {
+        let input = match store.input(day) {
+            Ok(input) => input,
+            Err(_) => {
+                eprintln!("Day {} - Part {}: no input found, skipping", day, part);
+                continue;
+            }
+        };
+
+        if let Err(e) = solutions::agreed_answer(day, part, &input) {
+            panic!("{}", e);
+        }
+
+        let mut group = c.benchmark_group(format!("Day {} - Part {}", day, part));
+        for solution in solutions::variants(day, part) {
+            let runner = solution.runner(&input).unwrap();
+            group.bench_function(solution.name.unwrap_or("(default)"), |b| {
+                b.iter(|| {
+                    runner.bench(|answer| {
+                        black_box(answer);
+                    })
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, variants);
+criterion_main!(benches);
@@ -1,37 +1,208 @@
+use std::{collections::VecDeque, io::BufRead};
+
+use anyhow::{Context, Result};
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
+use rand::Rng;
 
 #[aoc_generator(day1)]
 fn parse_input(input: &str) -> Vec<u32> {
     input.lines().map(|el| el.parse().unwrap()).collect()
 }
 
-#[aoc(day1, part1)]
-fn part1(input: &[u32]) -> u32 {
-    input
-        .iter()
-        .fold((0, 0), |acc, item| {
-            item.gt(&acc.1)
-                .then(|| (acc.0 + 1, *item))
-                .unwrap_or((acc.0, *item))
+/// Generate a random sonar sweep: a walk of depths that trends deeper.
+pub fn random_input<R: Rng>(rng: &mut R) -> String {
+    let mut depth: u32 = rng.gen_range(100..200);
+    (0..2000)
+        .map(|_| {
+            depth = depth.saturating_add_signed(rng.gen_range(-10..=20));
+            depth.to_string()
         })
-        .0
-        - 1
+        .join("\n")
 }
 
-#[aoc(day1, part2)]
-fn part2(input: &[u32]) -> u32 {
-    input
+/// Count how many times the sum of a sliding window of `window` depths is
+/// larger than the sum of the previous window.
+///
+/// Neighbouring windows share all but their first and last depths, so the
+/// comparison reduces to `depths[i + window] > depths[i]` and no sums are needed.
+pub fn count_increases(depths: &[u32], window: usize) -> usize {
+    assert!(window > 0, "window must contain at least one depth");
+    depths
         .iter()
-        .tuple_windows::<(_, _, _)>()
-        .fold((0, 0), |acc, item| {
-            let sum = item.0 + item.1 + item.2;
-            sum.gt(&acc.1)
-                .then(|| (acc.0 + 1, sum))
-                .unwrap_or((acc.0, sum))
-        })
-        .0
-        - 1
+        .zip(depths.iter().skip(window))
+        .filter(|(first, last)| last > first)
+        .count()
+}
+
+/// Counts increases in a stream of depths as they arrive, in the same way as
+/// [`count_increases`], while only holding on to the last `window` depths.
+#[derive(Clone, Debug)]
+pub struct SonarSweep {
+    window: usize,
+    recent: VecDeque<u32>,
+    increases: usize,
+}
+
+impl SonarSweep {
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "window must contain at least one depth");
+        Self {
+            window,
+            recent: VecDeque::with_capacity(window),
+            increases: 0,
+        }
+    }
+
+    /// Add the next depth, returning the number of increases seen so far.
+    pub fn push(&mut self, depth: u32) -> usize {
+        if self.recent.len() == self.window {
+            let first = self.recent.pop_front().unwrap();
+            if depth > first {
+                self.increases += 1;
+            }
+        }
+        self.recent.push_back(depth);
+        self.increases
+    }
+
+    /// The number of increases seen so far.
+    pub fn increases(&self) -> usize {
+        self.increases
+    }
+}
+
+/// Yield the running count of increases after each depth.
+pub fn sweep<I>(depths: I, window: usize) -> impl Iterator<Item = usize>
+where
+    I: IntoIterator<Item = u32>,
+{
+    let mut sonar = SonarSweep::new(window);
+    depths.into_iter().map(move |depth| sonar.push(depth))
+}
+
+/// Yield the running count of increases after each depth read from `reader`,
+/// one per line. Blank lines are skipped.
+pub fn sweep_reader<R: BufRead>(reader: R, window: usize) -> impl Iterator<Item = Result<usize>> {
+    let mut sonar = SonarSweep::new(window);
+    reader.lines().enumerate().filter_map(move |(i, line)| {
+        let line = match line.context("reading depths") {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        Some(
+            line.parse()
+                .with_context(|| format!("invalid depth '{}' on line {}", line, i + 1))
+                .map(|depth| sonar.push(depth)),
+        )
+    })
+}
+
+/// Thresholds used by [`anomalies`].
+#[derive(Clone, Copy, Debug)]
+pub struct AnomalyConfig {
+    /// The largest change between consecutive depths that isn't a jump.
+    pub jump: u32,
+    /// The number of equal depths in a row that make a plateau.
+    pub plateau: usize,
+    /// The number of preceding depths whose median gives the trend.
+    pub trend_window: usize,
+    /// How far a depth may stray from the trend before it's flagged.
+    pub trend_deviation: u32,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            jump: 50,
+            plateau: 3,
+            trend_window: 5,
+            trend_deviation: 50,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnomalyKind {
+    /// The depth moved too far from the previous one.
+    Jump { previous: u32 },
+    /// The depth starts a run of `length` equal depths.
+    Plateau { length: usize },
+    /// The depth is too far from the median of the depths before it.
+    OffTrend { median: u32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Anomaly {
+    pub index: usize,
+    pub depth: u32,
+    pub kind: AnomalyKind,
+}
+
+/// Find suspicious depths in a sonar sweep, ordered by index.
+pub fn anomalies(depths: &[u32], config: &AnomalyConfig) -> Vec<Anomaly> {
+    assert!(config.trend_window > 0, "trend needs at least one depth");
+    let jumps = depths
+        .iter()
+        .tuple_windows()
+        .enumerate()
+        .filter(|(_, (previous, depth))| previous.abs_diff(**depth) > config.jump)
+        .map(|(i, (previous, depth))| Anomaly {
+            index: i + 1,
+            depth: *depth,
+            kind: AnomalyKind::Jump {
+                previous: *previous,
+            },
+        });
+
+    let mut index = 0;
+    let plateaus = depths
+        .iter()
+        .dedup_with_count()
+        .filter_map(|(length, depth)| {
+            let start = index;
+            index += length;
+            (length >= config.plateau).then_some(Anomaly {
+                index: start,
+                depth: *depth,
+                kind: AnomalyKind::Plateau { length },
+            })
+        });
+
+    let off_trend = depths
+        .windows(config.trend_window + 1)
+        .enumerate()
+        .filter_map(|(i, window)| {
+            let (preceding, depth) = window.split_at(config.trend_window);
+            let mut preceding = preceding.to_vec();
+            preceding.sort_unstable();
+            let median = preceding[preceding.len() / 2];
+            (depth[0].abs_diff(median) > config.trend_deviation).then_some(Anomaly {
+                index: i + config.trend_window,
+                depth: depth[0],
+                kind: AnomalyKind::OffTrend { median },
+            })
+        });
+
+    jumps
+        .chain(plateaus)
+        .chain(off_trend)
+        .sorted_by_key(|anomaly| anomaly.index)
+        .collect()
+}
+
+#[aoc(day1, part1)]
+fn part1(input: &[u32]) -> usize {
+    count_increases(input, 1)
+}
+
+#[aoc(day1, part2)]
+fn part2(input: &[u32]) -> usize {
+    count_increases(input, 3)
 }
 
 #[cfg(test)]
@@ -54,4 +225,83 @@ mod tests {
             5
         );
     }
+
+    #[test]
+    fn test_count_increases() {
+        let depths = [199, 200, 208, 210, 200, 207, 240, 269, 260, 263];
+        assert_eq!(count_increases(&depths, 1), 7);
+        assert_eq!(count_increases(&depths, 3), 5);
+        assert_eq!(count_increases(&depths, 9), 1);
+        assert_eq!(count_increases(&depths, 10), 0);
+        assert_eq!(count_increases(&[0, 1], 1), 1);
+    }
+
+    #[test]
+    fn test_sweep() {
+        let depths = [199, 200, 208, 210, 200, 207, 240, 269, 260, 263];
+        assert_eq!(
+            sweep(depths, 1).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3, 3, 4, 5, 6, 6, 7]
+        );
+        assert_eq!(sweep(depths, 3).last(), Some(5));
+    }
+
+    #[test]
+    fn test_sweep_reader() {
+        let input = "199\n200\n208\n210\n200\n207\n240\n269\n260\n263\n\n";
+        assert_eq!(
+            sweep_reader(input.as_bytes(), 3).last().unwrap().unwrap(),
+            5
+        );
+        let err = sweep_reader("199\n2x0\n".as_bytes(), 1)
+            .collect::<Result<Vec<_>>>()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "invalid depth '2x0' on line 2");
+    }
+
+    #[test]
+    fn test_anomalies() {
+        let depths = [100, 102, 105, 300, 108, 110, 110, 110, 112];
+        assert_eq!(
+            anomalies(
+                &depths,
+                &AnomalyConfig {
+                    trend_window: 3,
+                    ..Default::default()
+                }
+            ),
+            vec![
+                Anomaly {
+                    index: 3,
+                    depth: 300,
+                    kind: AnomalyKind::Jump { previous: 105 },
+                },
+                Anomaly {
+                    index: 3,
+                    depth: 300,
+                    kind: AnomalyKind::OffTrend { median: 102 },
+                },
+                Anomaly {
+                    index: 4,
+                    depth: 108,
+                    kind: AnomalyKind::Jump { previous: 300 },
+                },
+                Anomaly {
+                    index: 5,
+                    depth: 110,
+                    kind: AnomalyKind::Plateau { length: 3 },
+                },
+            ]
+        );
+        assert!(anomalies(
+            &depths,
+            &AnomalyConfig {
+                jump: 200,
+                plateau: 4,
+                trend_window: 3,
+                trend_deviation: 200,
+            }
+        )
+        .is_empty());
+    }
 }
@@ -1,51 +1,461 @@
+use std::iter::Peekable;
+
+use anyhow::{bail, Result};
 use aoc_runner_derive::{aoc, aoc_generator};
-use serde::Deserialize;
+use itertools::Itertools;
+use rand::Rng;
 
-#[derive(Debug, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
-enum Direction {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
     Up,
     Down,
     Forward,
+    Backward,
+    /// Turn anticlockwise; the magnitude is the number of quarter turns.
+    Left,
+    /// Turn clockwise; the magnitude is the number of quarter turns.
+    Right,
 }
 
-#[derive(Debug, Deserialize, Eq, PartialEq)]
-struct Command {
-    direction: Direction,
-    magnitude: i32,
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Command {
+    pub direction: Direction,
+    pub magnitude: i32,
 }
 
-#[aoc_generator(day2)]
-fn parse_input(input: &str) -> Vec<Command> {
-    input
-        .lines()
-        .map(|line| serde_scan::from_str(line).unwrap())
+/// The way a submarine is facing on the horizontal plane.
+///
+/// Submarines start facing east, so moving forward without turning only ever
+/// changes the horizontal position, as in the original puzzle.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Heading {
+    #[default]
+    East,
+    North,
+    West,
+    South,
+}
+
+impl Heading {
+    const ANTICLOCKWISE: [Heading; 4] = [Self::East, Self::North, Self::West, Self::South];
+
+    /// Turn anticlockwise by the given number of quarter turns, which may be
+    /// negative to turn clockwise.
+    fn turn(self, quarter_turns: i32) -> Self {
+        let current = Self::ANTICLOCKWISE.iter().position(|h| *h == self).unwrap() as i32;
+        Self::ANTICLOCKWISE[(current + quarter_turns).rem_euclid(4) as usize]
+    }
+
+    /// The change in `(horizontal, lateral)` position from moving `distance`
+    /// in this direction.
+    fn step(self, distance: i32) -> (i32, i32) {
+        match self {
+            Self::East => (distance, 0),
+            Self::North => (0, distance),
+            Self::West => (-distance, 0),
+            Self::South => (0, -distance),
+        }
+    }
+}
+
+/// An interpretation of what the commands do to a submarine.
+pub trait Submarine {
+    fn apply(&mut self, command: &Command);
+
+    fn horizontal(&self) -> i32;
+
+    fn depth(&self) -> i32;
+
+    /// The aim, for interpretations that have one.
+    fn aim(&self) -> Option<i32> {
+        None
+    }
+}
+
+/// The interpretation from part 1, where up and down change the depth directly.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SimpleSub {
+    pub horizontal: i32,
+    /// The position at right angles to the starting heading, north positive.
+    pub lateral: i32,
+    pub depth: i32,
+    pub heading: Heading,
+}
+
+impl SimpleSub {
+    fn advance(&mut self, distance: i32) {
+        let (horizontal, lateral) = self.heading.step(distance);
+        self.horizontal += horizontal;
+        self.lateral += lateral;
+    }
+}
+
+impl Submarine for SimpleSub {
+    fn apply(&mut self, command: &Command) {
+        match command.direction {
+            Direction::Up => self.depth -= command.magnitude,
+            Direction::Down => self.depth += command.magnitude,
+            Direction::Forward => self.advance(command.magnitude),
+            Direction::Backward => self.advance(-command.magnitude),
+            Direction::Left => self.heading = self.heading.turn(command.magnitude),
+            Direction::Right => self.heading = self.heading.turn(-command.magnitude),
+        }
+    }
+
+    fn horizontal(&self) -> i32 {
+        self.horizontal
+    }
+
+    fn depth(&self) -> i32 {
+        self.depth
+    }
+}
+
+/// The interpretation from part 2, where up and down change the aim, and
+/// moving forward changes the depth according to the aim.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AimSub {
+    pub horizontal: i32,
+    /// The position at right angles to the starting heading, north positive.
+    pub lateral: i32,
+    pub depth: i32,
+    pub aim: i32,
+    pub heading: Heading,
+}
+
+impl AimSub {
+    fn advance(&mut self, distance: i32) {
+        let (horizontal, lateral) = self.heading.step(distance);
+        self.horizontal += horizontal;
+        self.lateral += lateral;
+        self.depth += distance * self.aim;
+    }
+}
+
+impl Submarine for AimSub {
+    fn apply(&mut self, command: &Command) {
+        match command.direction {
+            Direction::Up => self.aim -= command.magnitude,
+            Direction::Down => self.aim += command.magnitude,
+            Direction::Forward => self.advance(command.magnitude),
+            Direction::Backward => self.advance(-command.magnitude),
+            Direction::Left => self.heading = self.heading.turn(command.magnitude),
+            Direction::Right => self.heading = self.heading.turn(-command.magnitude),
+        }
+    }
+
+    fn horizontal(&self) -> i32 {
+        self.horizontal
+    }
+
+    fn depth(&self) -> i32 {
+        self.depth
+    }
+
+    fn aim(&self) -> Option<i32> {
+        Some(self.aim)
+    }
+}
+
+/// Apply every command to a fresh submarine, returning its final state.
+pub fn run<S: Submarine + Default>(commands: &[Command]) -> S {
+    let mut sub = S::default();
+    commands.iter().for_each(|command| sub.apply(command));
+    sub
+}
+
+/// Apply every command to a fresh submarine, returning its state before any
+/// commands and after each one.
+pub fn trajectory<S: Submarine + Default + Clone>(commands: &[Command]) -> Vec<S> {
+    let mut sub = S::default();
+    std::iter::once(sub.clone())
+        .chain(commands.iter().map(|command| {
+            sub.apply(command);
+            sub.clone()
+        }))
         .collect()
 }
 
-#[aoc(day2, part1)]
-fn part1(input: &[Command]) -> i32 {
-    let (horizontal, depth) =
-        input
+/// Export the trajectories of both interpretations as CSV, with one row per
+/// state. The aim column is empty for the part 1 interpretation.
+pub fn trajectory_csv(commands: &[Command]) -> String {
+    fn rows<'a, S: Submarine>(
+        model: &'a str,
+        states: &'a [S],
+    ) -> impl Iterator<Item = String> + 'a {
+        states.iter().enumerate().map(move |(step, sub)| {
+            format!(
+                "{},{},{},{},{}",
+                model,
+                step,
+                sub.horizontal(),
+                sub.depth(),
+                sub.aim().map(|aim| aim.to_string()).unwrap_or_default()
+            )
+        })
+    }
+    let (simple, aim) = (
+        trajectory::<SimpleSub>(commands),
+        trajectory::<AimSub>(commands),
+    );
+    std::iter::once("model,step,horizontal,depth,aim".to_string())
+        .chain(rows("part1", &simple))
+        .chain(rows("part2", &aim))
+        .map(|row| row + "\n")
+        .collect()
+}
+
+/// Render the trajectories of both interpretations as SVG polylines, looking
+/// at the submarine side-on with depth increasing downwards.
+///
+/// The image is stretched to fit both paths, since the part 2 interpretation
+/// tends to end up far deeper than the part 1 one.
+pub fn trajectory_svg(commands: &[Command]) -> String {
+    fn points<S: Submarine>(states: &[S]) -> Vec<(i32, i32)> {
+        states
             .iter()
-            .fold((0i32, 0i32), |acc, command| match command.direction {
-                Direction::Up => (acc.0, acc.1 - command.magnitude),
-                Direction::Down => (acc.0, acc.1 + command.magnitude),
-                Direction::Forward => (acc.0 + command.magnitude, acc.1),
+            .map(|sub| (sub.horizontal(), sub.depth()))
+            .collect()
+    }
+    let paths = [
+        (
+            "part1",
+            "steelblue",
+            points(&trajectory::<SimpleSub>(commands)),
+        ),
+        (
+            "part2",
+            "firebrick",
+            points(&trajectory::<AimSub>(commands)),
+        ),
+    ];
+    let all_points = paths.iter().flat_map(|(_, _, points)| points.iter());
+    let (min_x, max_x) = all_points
+        .clone()
+        .map(|p| p.0)
+        .minmax()
+        .into_option()
+        .unwrap();
+    let (min_y, max_y) = all_points.map(|p| p.1).minmax().into_option().unwrap();
+    let polylines = paths
+        .iter()
+        .map(|(model, colour, points)| {
+            format!(
+                "  <polyline id=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\" vector-effect=\"non-scaling-stroke\" points=\"{}\"/>\n",
+                model,
+                colour,
+                points.iter().map(|(x, y)| format!("{},{}", x, y)).join(" ")
+            )
+        })
+        .collect::<String>();
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"800\" height=\"600\" viewBox=\"{} {} {} {}\" preserveAspectRatio=\"none\">\n{}</svg>\n",
+        min_x,
+        min_y,
+        (max_x - min_x).max(1),
+        (max_y - min_y).max(1),
+        polylines
+    )
+}
+
+/// Split a line into tokens, treating braces as tokens of their own even when
+/// they're not surrounded by whitespace.
+fn tokenize_line(line: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    for word in line.split_whitespace() {
+        let mut start = 0;
+        for (i, c) in word.char_indices() {
+            if c == '{' || c == '}' {
+                if start < i {
+                    tokens.push(&word[start..i]);
+                }
+                tokens.push(&word[i..i + 1]);
+                start = i + 1;
+            }
+        }
+        if start < word.len() {
+            tokens.push(&word[start..]);
+        }
+    }
+    tokens
+}
+
+/// A recursive descent parser over the tokens of a mission script, each paired
+/// with its line number. Each line holds at most one command, and distances
+/// can't be negative.
+///
+/// ```text
+/// # Comments run to the end of the line.
+/// forward 5
+/// backward 2
+/// down 3
+/// up 1
+/// turn left      # a quarter turn anticlockwise
+/// turn right 2   # a half turn
+/// repeat 3 {
+///     forward 1
+/// }
+/// ```
+struct Parser<'a, I: Iterator<Item = (usize, &'a str)>> {
+    tokens: Peekable<I>,
+    /// The line of the last closing brace.
+    closed: usize,
+}
+
+/// The most commands a script may expand to, so that nested repeats can't
+/// exhaust memory.
+const MAX_COMMANDS: usize = 1_000_000;
+
+impl<'a, I: Iterator<Item = (usize, &'a str)>> Parser<'a, I> {
+    /// Parse commands until the end of the input or, inside a block opened on
+    /// line `open`, until the closing brace.
+    fn block(&mut self, open: Option<usize>) -> Result<Vec<Command>> {
+        let mut commands = Vec::new();
+        loop {
+            let (line, token) = match (self.tokens.next(), open) {
+                (Some(next), _) => next,
+                (None, None) => return Ok(commands),
+                (None, Some(open)) => bail!("line {}: unclosed '{{'", open),
+            };
+            let direction = match token {
+                "}" if open.is_some() => {
+                    self.closed = line;
+                    return Ok(commands);
+                }
+                "}" => bail!("line {}: unexpected '}}'", line),
+                "up" => Direction::Up,
+                "down" => Direction::Down,
+                "forward" => Direction::Forward,
+                "backward" => Direction::Backward,
+                "turn" => match self.tokens.next_if(|(l, _)| *l == line) {
+                    Some((_, "left")) => Direction::Left,
+                    Some((_, "right")) => Direction::Right,
+                    Some((_, other)) => {
+                        bail!(
+                            "line {}: expected 'left' or 'right', found '{}'",
+                            line,
+                            other
+                        )
+                    }
+                    None => bail!("line {}: expected 'left' or 'right' after 'turn'", line),
+                },
+                "repeat" => {
+                    let times: usize = self.number(line, token)?;
+                    match self.tokens.next_if(|(l, _)| *l == line) {
+                        Some((_, "{")) => {}
+                        _ => bail!("line {}: expected '{{' after 'repeat {}'", line, times),
+                    }
+                    let body = self.block(Some(line))?;
+                    if body.len().saturating_mul(times) > MAX_COMMANDS - commands.len() {
+                        bail!(
+                            "line {}: 'repeat {}' expands to more than {} commands",
+                            line,
+                            times,
+                            MAX_COMMANDS
+                        );
+                    }
+                    for _ in 0..times {
+                        commands.extend_from_slice(&body);
+                    }
+                    self.end_of_command(self.closed)?;
+                    continue;
+                }
+                other => bail!("line {}: unknown command '{}'", line, other),
+            };
+            let magnitude = match direction {
+                // Turns are a single quarter turn unless told otherwise.
+                Direction::Left | Direction::Right => self
+                    .tokens
+                    .next_if(|(l, token)| *l == line && token.parse::<u16>().is_ok())
+                    .map_or(1, |(_, token)| token.parse().unwrap()),
+                _ => self.magnitude(line, token)?,
+            };
+            self.end_of_command(line)?;
+            if commands.len() == MAX_COMMANDS {
+                bail!("line {}: more than {} commands", line, MAX_COMMANDS);
+            }
+            commands.push(Command {
+                direction,
+                magnitude,
             });
-    horizontal * depth
+        }
+    }
+
+    /// Check nothing but a closing brace follows a command on its line.
+    fn end_of_command(&mut self, line: usize) -> Result<()> {
+        match self
+            .tokens
+            .next_if(|(l, token)| *l == line && *token != "}")
+        {
+            Some((_, token)) => bail!("line {}: unexpected '{}' after a command", line, token),
+            None => Ok(()),
+        }
+    }
+
+    /// Parse the distance following `after` on the same line, which can't be
+    /// negative.
+    fn magnitude(&mut self, line: usize, after: &str) -> Result<i32> {
+        let magnitude: u32 = self.number(line, after)?;
+        match i32::try_from(magnitude) {
+            Ok(magnitude) => Ok(magnitude),
+            Err(_) => bail!("line {}: {} is too large", line, magnitude),
+        }
+    }
+
+    /// Parse the number following `after` on the same line.
+    fn number<T: std::str::FromStr>(&mut self, line: usize, after: &str) -> Result<T> {
+        match self.tokens.next_if(|(l, _)| *l == line) {
+            Some((_, token)) => match token.parse() {
+                Ok(n) => Ok(n),
+                Err(_) => bail!(
+                    "line {}: expected a number after '{}', found '{}'",
+                    line,
+                    after,
+                    token
+                ),
+            },
+            None => bail!("line {}: expected a number after '{}'", line, after),
+        }
+    }
+}
+
+#[aoc_generator(day2)]
+fn parse_input(input: &str) -> Result<Vec<Command>> {
+    let tokens = input.lines().enumerate().flat_map(|(i, line)| {
+        let code = line.split('#').next().unwrap();
+        tokenize_line(code)
+            .into_iter()
+            .map(move |token| (i + 1, token))
+    });
+    Parser {
+        tokens: tokens.peekable(),
+        closed: 0,
+    }
+    .block(None)
+}
+
+/// Generate a random list of commands.
+///
+/// Kept short enough that neither interpretation can overflow an `i32`.
+pub fn random_input<R: Rng>(rng: &mut R) -> String {
+    (0..100)
+        .map(|_| {
+            let direction = ["up", "down", "forward"][rng.gen_range(0..3)];
+            format!("{} {}", direction, rng.gen_range(1..10))
+        })
+        .join("\n")
+}
+
+#[aoc(day2, part1)]
+fn part1(input: &[Command]) -> i32 {
+    let sub: SimpleSub = run(input);
+    sub.horizontal() * sub.depth()
 }
 
 #[aoc(day2, part2)]
 fn part2(input: &[Command]) -> i32 {
-    let (horizontal, depth, _) = input.iter().fold((0i32, 0i32, 0i32), |(h, d, a), command| {
-        match command.direction {
-            Direction::Up => (h, d, a - command.magnitude),
-            Direction::Down => (h, d, a + command.magnitude),
-            Direction::Forward => (h + command.magnitude, d + (command.magnitude * a), a),
-        }
-    });
-    horizontal * depth
+    let sub: AimSub = run(input);
+    sub.horizontal() * sub.depth()
 }
 
 #[cfg(test)]
@@ -62,7 +472,7 @@ up 3
 down 8
 forward 2";
         assert_eq!(
-            parse_input(input),
+            parse_input(input).unwrap(),
             vec![
                 Command {
                     direction: Direction::Forward,
@@ -157,4 +567,178 @@ forward 2";
             900
         );
     }
+
+    #[test]
+    fn test_trajectory() {
+        let commands = parse_input("forward 5\ndown 5\nforward 8").unwrap();
+        assert_eq!(
+            trajectory::<AimSub>(&commands),
+            vec![
+                AimSub::default(),
+                AimSub {
+                    horizontal: 5,
+                    depth: 0,
+                    aim: 0,
+                    ..Default::default()
+                },
+                AimSub {
+                    horizontal: 5,
+                    depth: 0,
+                    aim: 5,
+                    ..Default::default()
+                },
+                AimSub {
+                    horizontal: 13,
+                    depth: 40,
+                    aim: 5,
+                    ..Default::default()
+                },
+            ]
+        );
+        assert_eq!(
+            run::<SimpleSub>(&commands),
+            SimpleSub {
+                horizontal: 13,
+                depth: 5,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_extended() {
+        let commands = parse_input(
+            "# a square, twice
+repeat 2 {
+    repeat 4 { forward 2
+        turn left }   # trailing comment
+}
+backward 1
+turn right 2
+down 3",
+        )
+        .unwrap();
+        assert_eq!(commands.len(), 19);
+        assert_eq!(
+            commands[..2],
+            [
+                Command {
+                    direction: Direction::Forward,
+                    magnitude: 2
+                },
+                Command {
+                    direction: Direction::Left,
+                    magnitude: 1
+                },
+            ]
+        );
+        assert_eq!(
+            commands[16..],
+            [
+                Command {
+                    direction: Direction::Backward,
+                    magnitude: 1
+                },
+                Command {
+                    direction: Direction::Right,
+                    magnitude: 2
+                },
+                Command {
+                    direction: Direction::Down,
+                    magnitude: 3
+                },
+            ]
+        );
+        assert_eq!(
+            run::<SimpleSub>(&commands),
+            SimpleSub {
+                horizontal: -1,
+                lateral: 0,
+                depth: 3,
+                heading: Heading::West,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input| parse_input(input).unwrap_err().to_string();
+        assert_eq!(
+            error("forward 5\nsideways 2"),
+            "line 2: unknown command 'sideways'"
+        );
+        assert_eq!(
+            error("forward\n5"),
+            "line 1: expected a number after 'forward'"
+        );
+        assert_eq!(
+            error("down five"),
+            "line 1: expected a number after 'down', found 'five'"
+        );
+        assert_eq!(
+            error("turn around"),
+            "line 1: expected 'left' or 'right', found 'around'"
+        );
+        assert_eq!(error("up 1\nrepeat 2 {\n  up 1\n"), "line 2: unclosed '{'");
+        assert_eq!(error("up 1\n}"), "line 2: unexpected '}'");
+        assert_eq!(
+            error("repeat 2\n{ up 1 }"),
+            "line 1: expected '{' after 'repeat 2'"
+        );
+        assert_eq!(
+            error("up 1\nforward 5 down 3"),
+            "line 2: unexpected 'down' after a command"
+        );
+        assert_eq!(
+            error("repeat 2 {\n  up 1\n} up 1"),
+            "line 3: unexpected 'up' after a command"
+        );
+        assert_eq!(
+            error("turn left -1"),
+            "line 1: unexpected '-1' after a command"
+        );
+        assert_eq!(
+            error("forward -5"),
+            "line 1: expected a number after 'forward', found '-5'"
+        );
+        assert_eq!(
+            error("forward 3000000000"),
+            "line 1: 3000000000 is too large"
+        );
+        assert_eq!(
+            error("up 1\nrepeat 4000000000 { forward 1 }"),
+            "line 2: 'repeat 4000000000' expands to more than 1000000 commands"
+        );
+        assert_eq!(
+            error("repeat 1000 {\n  repeat 1000 {\n    repeat 1000 { up 1 }\n  }\n}"),
+            "line 1: 'repeat 1000' expands to more than 1000000 commands"
+        );
+    }
+
+    #[test]
+    fn test_trajectory_csv() {
+        let commands = parse_input("forward 5\ndown 5\nforward 8").unwrap();
+        assert_eq!(
+            trajectory_csv(&commands),
+            "model,step,horizontal,depth,aim
+part1,0,0,0,
+part1,1,5,0,
+part1,2,5,5,
+part1,3,13,5,
+part2,0,0,0,0
+part2,1,5,0,0
+part2,2,5,0,5
+part2,3,13,40,5
+"
+        );
+    }
+
+    #[test]
+    fn test_trajectory_svg() {
+        let commands = parse_input("forward 5\ndown 5\nforward 8").unwrap();
+        let svg = trajectory_svg(&commands);
+        assert!(svg.contains("viewBox=\"0 0 13 40\""));
+        assert!(svg.contains("points=\"0,0 5,0 5,5 13,5\""));
+        assert!(svg.contains("points=\"0,0 5,0 5,0 13,40\""));
+    }
 }
@@ -1,54 +1,357 @@
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use anyhow::{bail, Context, Result};
 use aoc_runner_derive::{aoc, aoc_generator};
+use itertools::Itertools;
+use num_bigint::BigUint;
+use rand::Rng;
+
+/// A line of a diagnostic report, of any width, stored as a bitset.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Bits {
+    /// Packed least significant bit first, so bit `i` (from 0) lives in
+    /// `words[i / 64]`. Any bits beyond `width` are always unset.
+    words: Vec<u64>,
+    width: usize,
+}
+
+impl Bits {
+    fn zeros(width: usize) -> Self {
+        Self {
+            words: vec![0; width.div_ceil(64)],
+            width,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Whether a bit is set, counting from 1 at the least significant bit.
+    pub fn is_set(&self, which: usize) -> bool {
+        let i = which - 1;
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    fn set(&mut self, which: usize, value: bool) {
+        let i = which - 1;
+        if value {
+            self.words[i / 64] |= 1 << (i % 64);
+        } else {
+            self.words[i / 64] &= !(1 << (i % 64));
+        }
+    }
+
+    /// Flip every bit within the width.
+    pub fn complement(&self) -> Self {
+        let mut words: Vec<u64> = self.words.iter().map(|w| !w).collect();
+        if !self.width.is_multiple_of(64) {
+            *words.last_mut().unwrap() &= (1 << (self.width % 64)) - 1;
+        }
+        Self {
+            words,
+            width: self.width,
+        }
+    }
+
+    pub fn to_biguint(&self) -> BigUint {
+        BigUint::from_slice(
+            &self
+                .words
+                .iter()
+                .flat_map(|w| [*w as u32, (*w >> 32) as u32])
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+
+impl FromStr for Bits {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut bits = Self::zeros(s.len());
+        for (i, c) in s.chars().rev().enumerate() {
+            match c {
+                '0' => {}
+                '1' => bits.set(i + 1, true),
+                _ => bail!("invalid bit '{}'", c),
+            }
+        }
+        Ok(bits)
+    }
+}
+
+impl fmt::Display for Bits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (1..=self.width)
+            .rev()
+            .try_for_each(|which| write!(f, "{}", self.is_set(which) as u8))
+    }
+}
+
+/// A diagnostic report whose lines all have the same width.
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub width: usize,
+    pub lines: Vec<Bits>,
+}
 
 #[aoc_generator(day3)]
-fn parse_input(input: &str) -> (u32, Vec<u32>) {
-    let numbers = input
+fn parse_input(input: &str) -> Result<Report> {
+    let lines = input
         .lines()
-        .map(|x| u32::from_str_radix(x, 2).unwrap())
-        .collect();
-    (input.lines().next().unwrap().len() as u32, numbers)
+        .enumerate()
+        .map(|(i, line)| {
+            line.trim()
+                .parse::<Bits>()
+                .with_context(|| format!("line {}", i + 1))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let width = match lines.first() {
+        Some(line) => line.width(),
+        None => bail!("empty report"),
+    };
+    if let Some((i, line)) = lines.iter().find_position(|line| line.width() != width) {
+        bail!(
+            "line {}: expected {} bits like the first line, found {}",
+            i + 1,
+            width,
+            line.width()
+        );
+    }
+    Ok(Report { width, lines })
+}
+
+fn count_ones(input: &[Bits], which: usize) -> usize {
+    input.iter().filter(|x| x.is_set(which)).count()
+}
+
+/// Which bit value is more or less common among some lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Commonality {
+    Most,
+    Least,
+}
+
+/// A rule choosing which bit to keep at each position while searching for a
+/// rating.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitCriteria {
+    pub keep: Commonality,
+    /// The bit to keep when there are as many ones as zeros.
+    pub tie: bool,
+}
+
+impl BitCriteria {
+    /// Keep the most common bit, or 1 on a tie.
+    pub const OXYGEN: Self = Self {
+        keep: Commonality::Most,
+        tie: true,
+    };
+    /// Keep the least common bit, or 0 on a tie.
+    pub const CO2: Self = Self {
+        keep: Commonality::Least,
+        tie: false,
+    };
+
+    /// The bit to keep given how many of `total` candidates have it set.
+    pub fn bit(&self, ones: usize, total: usize) -> bool {
+        match ones.cmp(&(total - ones)) {
+            Ordering::Equal => self.tie,
+            Ordering::Greater => self.keep == Commonality::Most,
+            Ordering::Less => self.keep == Commonality::Least,
+        }
+    }
+}
+
+pub fn gamma(report: &Report) -> Bits {
+    let mut gamma = Bits::zeros(report.width);
+    for which in 1..=report.width {
+        gamma.set(
+            which,
+            BitCriteria::OXYGEN.bit(count_ones(&report.lines, which), report.lines.len()),
+        );
+    }
+    gamma
+}
+
+pub fn epsilon(report: &Report) -> Bits {
+    gamma(report).complement()
+}
+
+/// Count the ones in every column of the report in a single pass.
+///
+/// The counts are kept bit-sliced: `planes[k]` holds bit `k` of the count of
+/// every column of a word, so adding a line is a ripple-carry add of its
+/// words into the planes, 64 columns at a time.
+pub fn column_counts(report: &Report) -> Vec<usize> {
+    let words = report.width.div_ceil(64);
+    let depth = (usize::BITS - report.lines.len().leading_zeros()) as usize;
+    let mut planes = vec![vec![0u64; depth]; words];
+    for line in &report.lines {
+        for (word, planes) in line.words.iter().zip(&mut planes) {
+            let mut carry = *word;
+            for plane in planes.iter_mut() {
+                if carry == 0 {
+                    break;
+                }
+                let sum = *plane ^ carry;
+                carry &= *plane;
+                *plane = sum;
+            }
+        }
+    }
+    (0..report.width)
+        .map(|i| {
+            planes[i / 64]
+                .iter()
+                .enumerate()
+                .map(|(k, plane)| (((plane >> (i % 64)) & 1) as usize) << k)
+                .sum()
+        })
+        .collect()
+}
+
+/// Like [`gamma`], but from [`column_counts`] instead of scanning the report
+/// once per column.
+pub fn gamma_popcount(report: &Report) -> Bits {
+    let mut gamma = Bits::zeros(report.width);
+    for (i, ones) in column_counts(report).into_iter().enumerate() {
+        gamma.set(i + 1, BitCriteria::OXYGEN.bit(ones, report.lines.len()));
+    }
+    gamma
+}
+
+/// Filter the report one bit at a time, from the most significant, keeping
+/// the lines whose bit matches the one chosen by `criteria` for the remaining
+/// candidates. Returns `None` if every candidate gets filtered out.
+pub fn rating(report: &Report, criteria: BitCriteria) -> Option<Bits> {
+    let mut candidates = report.lines.clone();
+    for which in (1..=report.width).rev() {
+        if candidates.len() <= 1 {
+            break;
+        }
+        let keep = criteria.bit(count_ones(&candidates, which), candidates.len());
+        candidates.retain(|x| x.is_set(which) == keep);
+    }
+    candidates.into_iter().next()
+}
+
+pub fn oxygen_rating(report: &Report) -> Option<Bits> {
+    rating(report, BitCriteria::OXYGEN)
 }
 
-fn is_bit_set(number: u32, which: u32) -> bool {
-    number & (1 << (which - 1)) != 0
+pub fn co2_rating(report: &Report) -> Option<Bits> {
+    rating(report, BitCriteria::CO2)
 }
 
-fn more_bits_set(input: &[u32], which: u32) -> bool {
-    input
-        .iter()
-        .map(|x| is_bit_set(*x, which) as u32)
-        .sum::<u32>() as f32
-        >= (input.len() as f32 / 2.)
+/// A binary trie of the lines in a report, most significant bit first, where
+/// each node knows how many lines pass through it.
+pub struct Trie {
+    width: usize,
+    /// Node 0 is the root; children are indices into `nodes`, by bit value.
+    nodes: Vec<TrieNode>,
+}
+
+#[derive(Default)]
+struct TrieNode {
+    count: usize,
+    children: [Option<usize>; 2],
+}
+
+impl Trie {
+    pub fn new(report: &Report) -> Self {
+        let mut nodes = vec![TrieNode::default()];
+        for line in &report.lines {
+            let mut node = 0;
+            nodes[node].count += 1;
+            for which in (1..=report.width).rev() {
+                let bit = line.is_set(which) as usize;
+                node = match nodes[node].children[bit] {
+                    Some(child) => child,
+                    None => {
+                        nodes.push(TrieNode::default());
+                        nodes[node].children[bit] = Some(nodes.len() - 1);
+                        nodes.len() - 1
+                    }
+                };
+                nodes[node].count += 1;
+            }
+        }
+        Self {
+            width: report.width,
+            nodes,
+        }
+    }
+
+    fn count(&self, child: Option<usize>) -> usize {
+        child.map_or(0, |node| self.nodes[node].count)
+    }
+
+    /// Walk down the trie the same way [`rating`] filters the report.
+    pub fn rating(&self, criteria: BitCriteria) -> Option<Bits> {
+        let mut bits = Bits::zeros(self.width);
+        let mut node = 0;
+        for which in (1..=self.width).rev() {
+            let TrieNode { count, children } = &self.nodes[node];
+            let bit = if *count > 1 {
+                criteria.bit(self.count(children[1]), *count)
+            } else {
+                // A single candidate left, so follow it to the end.
+                children[1].is_some()
+            };
+            bits.set(which, bit);
+            node = children[bit as usize]?;
+        }
+        Some(bits)
+    }
+}
+
+/// Generate a random diagnostic report of distinct 12-bit numbers.
+///
+/// Real reports are chosen so that the CO2 filter never discards every
+/// candidate, which happens when all remaining numbers share a bit; reports
+/// where that would happen are regenerated.
+pub fn random_input<R: Rng>(rng: &mut R) -> String {
+    loop {
+        let input = rand::seq::index::sample(rng, 1 << 12, 1000)
+            .into_iter()
+            .map(|x| format!("{:012b}", x))
+            .join("\n");
+        if co2_rating(&parse_input(&input).unwrap()).is_some() {
+            return input;
+        }
+    }
 }
 
 #[aoc(day3, part1)]
-fn part1((max_length, input): &(u32, Vec<u32>)) -> u32 {
-    let gamma: u32 = (1..(max_length + 1))
-        .map(|k| more_bits_set(input, k))
-        .rev()
-        .fold(0, |acc, b| (acc << 1) + b as u32);
-    let epsilon = !gamma ^ (u32::MAX << max_length);
-    gamma * epsilon
+fn part1(input: &Report) -> BigUint {
+    gamma(input).to_biguint() * epsilon(input).to_biguint()
+}
+
+#[aoc(day3, part1, Popcount)]
+fn part1_popcount(input: &Report) -> BigUint {
+    let gamma = gamma_popcount(input);
+    gamma.to_biguint() * gamma.complement().to_biguint()
 }
 
 #[aoc(day3, part2)]
-fn part2((max_length, input): &(u32, Vec<u32>)) -> u32 {
-    let (mut oxy_candidates, mut co2_candidates) = (input.clone(), input.clone());
-    let (oxy, co2) =
-        (1..(max_length + 1))
-            .rev()
-            .fold((&mut oxy_candidates, &mut co2_candidates), |acc, i| {
-                let (should_keep_oxy, should_keep_co2) =
-                    (more_bits_set(acc.0, i), !more_bits_set(acc.1, i));
-                if acc.0.len() > 1 {
-                    acc.0.retain(|x| should_keep_oxy == is_bit_set(*x, i));
-                }
-                if acc.1.len() > 1 {
-                    acc.1.retain(|x| should_keep_co2 == is_bit_set(*x, i));
-                }
-                (acc.0, acc.1)
-            });
-    oxy[0] * co2[0]
+fn part2(input: &Report) -> Result<BigUint> {
+    let oxygen = oxygen_rating(input).context("no oxygen generator rating")?;
+    let co2 = co2_rating(input).context("no CO2 scrubber rating")?;
+    Ok(oxygen.to_biguint() * co2.to_biguint())
+}
+
+#[aoc(day3, part2, Trie)]
+fn part2_trie(input: &Report) -> Result<BigUint> {
+    let trie = Trie::new(input);
+    let oxygen = trie
+        .rating(BitCriteria::OXYGEN)
+        .context("no oxygen generator rating")?;
+    let co2 = trie
+        .rating(BitCriteria::CO2)
+        .context("no CO2 scrubber rating")?;
+    Ok(oxygen.to_biguint() * co2.to_biguint())
 }
 
 #[cfg(test)]
@@ -57,17 +360,20 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_is_bit_set() {
-        assert_eq!(is_bit_set(123, 1), true);
-        assert_eq!(is_bit_set(127, 3), true);
-        assert_eq!(is_bit_set(127, 8), false);
+    fn test_is_set() {
+        let bits: Bits = "1111011".parse().unwrap();
+        assert!(bits.is_set(1));
+        assert!(!bits.is_set(3));
+        assert!(bits.is_set(7));
+        assert_eq!(bits.to_biguint(), BigUint::from(123u32));
     }
 
     #[test]
     fn test_part1() {
         assert_eq!(
-            part1(&parse_input(
-                "00100
+            part1(
+                &parse_input(
+                    "00100
 11110
 10110
 10111
@@ -80,16 +386,19 @@ mod tests {
 00010
 01010
 "
-            )),
-            198
+                )
+                .unwrap()
+            ),
+            BigUint::from(198u32)
         );
     }
 
     #[test]
     fn test_part2() {
         assert_eq!(
-            part2(&parse_input(
-                "00100
+            part2(
+                &parse_input(
+                    "00100
 11110
 10110
 10111
@@ -102,8 +411,126 @@ mod tests {
 00010
 01010
 "
-            )),
-            230
+                )
+                .unwrap()
+            )
+            .unwrap(),
+            BigUint::from(230u32)
+        );
+    }
+
+    #[test]
+    fn test_wide_report() {
+        let report = parse_input(
+            "1000000000000000000000000000000000000000000000000000000000000000000001
+1000000000000000000000000000000000000000000000000000000000000000000000
+0000000000000000000000000000000000000000000000000000000000000000000001",
+        )
+        .unwrap();
+        assert_eq!(report.width, 70);
+        assert_eq!(
+            gamma(&report).to_string(),
+            "1000000000000000000000000000000000000000000000000000000000000000000001"
+        );
+        assert_eq!(
+            epsilon(&report).to_string(),
+            "0111111111111111111111111111111111111111111111111111111111111111111110"
+        );
+        assert_eq!(
+            epsilon(&report).to_biguint(),
+            (BigUint::from(1u32) << 69) - 2u32
         );
+        assert_eq!(
+            oxygen_rating(&report).unwrap().to_string(),
+            "1000000000000000000000000000000000000000000000000000000000000000000001"
+        );
+        assert_eq!(
+            co2_rating(&report).unwrap().to_string(),
+            "0000000000000000000000000000000000000000000000000000000000000000000001"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_input("0101\n011\n").unwrap_err().to_string(),
+            "line 2: expected 4 bits like the first line, found 3"
+        );
+        assert_eq!(
+            format!("{:#}", parse_input("0101\n0121\n").unwrap_err()),
+            "line 2: invalid bit '2'"
+        );
+    }
+
+    #[test]
+    fn test_trie_rating() {
+        let report = parse_input(
+            "00100\n11110\n10110\n10111\n10101\n01111\n00111\n11100\n10000\n11001\n00010\n01010",
+        )
+        .unwrap();
+        let trie = Trie::new(&report);
+        assert_eq!(
+            trie.rating(BitCriteria::OXYGEN).unwrap().to_string(),
+            "10111"
+        );
+        assert_eq!(trie.rating(BitCriteria::CO2).unwrap().to_string(), "01010");
+        assert_eq!(part2_trie(&report).unwrap(), BigUint::from(230u32));
+
+        // Every remaining candidate shares the first bit, so the CO2 filter
+        // empties just like the scanning implementation does.
+        let report = parse_input("00\n01\n01").unwrap();
+        assert_eq!(co2_rating(&report), None);
+        assert_eq!(Trie::new(&report).rating(BitCriteria::CO2), None);
+    }
+
+    #[test]
+    fn test_bit_criteria() {
+        let least_tie_one = BitCriteria {
+            keep: Commonality::Least,
+            tie: true,
+        };
+        assert!(least_tie_one.bit(2, 5));
+        assert!(!least_tie_one.bit(3, 5));
+        assert!(least_tie_one.bit(2, 4));
+        assert!(!BitCriteria::CO2.bit(2, 4));
+        assert!(BitCriteria::OXYGEN.bit(2, 4));
+
+        // Exact even for counts an f32 can't represent.
+        let total = (1 << 25) + 2;
+        assert!(!BitCriteria::OXYGEN.bit(total / 2 - 1, total));
+        assert!(BitCriteria::OXYGEN.bit(total / 2, total));
+    }
+
+    #[test]
+    fn test_rating_with_other_criteria() {
+        let report = parse_input(
+            "00100\n11110\n10110\n10111\n10101\n01111\n00111\n11100\n10000\n11001\n00010\n01010",
+        )
+        .unwrap();
+        let trie = Trie::new(&report);
+        for (keep, tie, expected) in [
+            (Commonality::Most, false, "10110"),
+            (Commonality::Least, true, "01111"),
+        ] {
+            let criteria = BitCriteria { keep, tie };
+            assert_eq!(rating(&report, criteria).unwrap().to_string(), expected);
+            assert_eq!(trie.rating(criteria).unwrap().to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_column_counts() {
+        let report = parse_input(
+            "00100\n11110\n10110\n10111\n10101\n01111\n00111\n11100\n10000\n11001\n00010\n01010",
+        )
+        .unwrap();
+        // Least significant column first.
+        assert_eq!(column_counts(&report), vec![5, 7, 8, 5, 7]);
+        assert_eq!(part1_popcount(&report), BigUint::from(198u32));
+
+        let line = "1".repeat(130);
+        let report = parse_input(&[line.as_str(); 7].join("\n")).unwrap();
+        assert_eq!(column_counts(&report), vec![7; 130]);
+        assert_eq!(gamma_popcount(&report), gamma(&report));
     }
 }
@@ -1,27 +1,60 @@
-use anyhow::Result;
+use std::{collections::VecDeque, fmt};
+
+use anyhow::{bail, Context, Result};
 use itertools::Itertools;
+use rand::{seq::SliceRandom, Rng};
 
 use aoc_runner_derive::{aoc, aoc_generator};
 
 /// The value of a cell.
-#[derive(Clone, Copy, Debug, PartialEq)]
-struct Value(u8);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Value(pub u8);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Row(pub u8);
 
-#[derive(Clone, Copy, Debug)]
-struct Row(u8);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Column(pub u8);
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-struct Column(u8);
+/// A set of cells that wins the game once they have all been drawn.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WinPattern {
+    /// Any full row.
+    Rows,
+    /// Any full column.
+    Columns,
+    /// Either full diagonal, on non-empty square boards only.
+    Diagonals,
+    /// The four corners.
+    Corners,
+    /// Every cell on the board.
+    Blackout,
+    /// A custom set of cells; it never wins on boards too small to hold it.
+    Mask(Vec<(Row, Column)>),
+}
 
-#[derive(Clone, Copy, Debug)]
-enum Winner {
+impl WinPattern {
+    /// The standard rules: any full row or column.
+    pub const STANDARD: &'static [WinPattern] = &[WinPattern::Rows, WinPattern::Columns];
+}
+
+/// How a board won, given by the pattern that fired.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Winner {
     Row(Row),
     Column(Column),
+    /// The diagonal from the top left to the bottom right.
+    Diagonal,
+    /// The diagonal from the top right to the bottom left.
+    AntiDiagonal,
+    Corners,
+    Blackout,
+    /// The mask at this index in the patterns being checked.
+    Mask(usize),
 }
 
 #[derive(Clone, Debug)]
-struct Cell {
-    #[allow(dead_code)]
+pub struct Cell {
     row: Row,
     column: Column,
     value: Value,
@@ -39,166 +72,621 @@ impl Cell {
     }
 }
 
+/// A rectangular board, with its cells stored row by row.
 #[derive(Clone, Debug)]
-struct Board {
+pub struct Board {
     cells: Vec<Cell>,
+    rows: u8,
+    columns: u8,
 }
 
 impl FromIterator<Cell> for Board {
+    /// Collect cells given in row-major order into a board, inferring its size
+    /// from the last cell.
     fn from_iter<T>(iter: T) -> Self
     where
         T: IntoIterator<Item = Cell>,
     {
+        let cells: Vec<Cell> = iter.into_iter().collect();
+        let (rows, columns) = cells
+            .last()
+            .map_or((0, 0), |c| (c.row.0 + 1, c.column.0 + 1));
+        debug_assert_eq!(cells.len(), rows as usize * columns as usize);
         Self {
-            cells: iter.into_iter().collect(),
+            cells,
+            rows,
+            columns,
         }
     }
 }
 
 impl Board {
-    fn draw(&mut self, v: Value) {
-        if let Some(c) = self.cells.iter_mut().find(|c| c.value == v) {
+    /// Mark every cell holding the value as drawn.
+    pub fn draw(&mut self, v: Value) {
+        for c in self.cells.iter_mut().filter(|c| c.value == v) {
             c.drawn = true
         }
     }
 
+    fn cell(&self, i: Row, j: Column) -> Option<&Cell> {
+        (i.0 < self.rows && j.0 < self.columns)
+            .then(|| &self.cells[i.0 as usize * self.columns as usize + j.0 as usize])
+    }
+
     fn row(&self, i: Row) -> impl Iterator<Item = &Cell> {
-        self.cells.iter().skip(i.0 as usize * 5).take(5)
+        let columns = self.columns as usize;
+        self.cells.iter().skip(i.0 as usize * columns).take(columns)
     }
 
     fn column(&self, j: Column) -> impl Iterator<Item = &Cell> {
         self.cells.iter().filter(move |c| c.column == j)
     }
 
-    fn check(&self) -> Option<Winner> {
-        (0..5)
-            .find_map(|i| {
-                self.row(Row(i))
-                    .all(|x| x.drawn)
-                    .then(|| Winner::Row(Row(i)))
-            })
-            .or_else(|| {
-                (0..5).find_map(|j| {
+    /// Whether every one of the given cells has been drawn; cells outside
+    /// the board are never drawn.
+    fn all_drawn(&self, mut cells: impl Iterator<Item = (Row, Column)>) -> bool {
+        cells.all(|(i, j)| self.cell(i, j).is_some_and(|c| c.drawn))
+    }
+
+    /// Check the board against the standard rules.
+    pub fn check(&self) -> Option<Winner> {
+        self.check_with(WinPattern::STANDARD)
+    }
+
+    /// Check the board against each pattern in turn, returning how it won
+    /// with the first one that fired.
+    pub fn check_with(&self, patterns: &[WinPattern]) -> Option<Winner> {
+        let (rows, columns) = (self.rows, self.columns);
+        patterns
+            .iter()
+            .enumerate()
+            .find_map(|(index, pattern)| match pattern {
+                WinPattern::Rows => (0..rows).find_map(|i| {
+                    self.row(Row(i))
+                        .all(|x| x.drawn)
+                        .then_some(Winner::Row(Row(i)))
+                }),
+                WinPattern::Columns => (0..columns).find_map(|j| {
                     self.column(Column(j))
                         .all(|x| x.drawn)
-                        .then(|| Winner::Column(Column(j)))
-                })
+                        .then_some(Winner::Column(Column(j)))
+                }),
+                WinPattern::Diagonals if rows == columns && rows > 0 => {
+                    let n = rows;
+                    if self.all_drawn((0..n).map(|k| (Row(k), Column(k)))) {
+                        Some(Winner::Diagonal)
+                    } else if self.all_drawn((0..n).map(|k| (Row(k), Column(n - 1 - k)))) {
+                        Some(Winner::AntiDiagonal)
+                    } else {
+                        None
+                    }
+                }
+                WinPattern::Diagonals => None,
+                WinPattern::Corners if rows == 0 || columns == 0 => None,
+                WinPattern::Corners => {
+                    let (last_row, last_column) = (Row(rows - 1), Column(columns - 1));
+                    self.all_drawn(
+                        [
+                            (Row(0), Column(0)),
+                            (Row(0), last_column),
+                            (last_row, Column(0)),
+                            (last_row, last_column),
+                        ]
+                        .into_iter(),
+                    )
+                    .then_some(Winner::Corners)
+                }
+                WinPattern::Blackout => self
+                    .cells
+                    .iter()
+                    .all(|c| c.drawn)
+                    .then_some(Winner::Blackout),
+                WinPattern::Mask(cells) => self
+                    .all_drawn(cells.iter().copied())
+                    .then_some(Winner::Mask(index)),
             })
     }
 
-    fn sum_of_undrawn_numbers(&self) -> usize {
+    pub fn sum_of_undrawn_numbers(&self) -> usize {
         self.cells
             .iter()
-            .filter_map(|cell| (!cell.drawn).then(|| cell.value.0 as usize))
+            .filter(|cell| !cell.drawn)
+            .map(|cell| cell.value.0 as usize)
             .sum()
     }
 }
 
 type Input = (Vec<Value>, Vec<Board>);
 
-#[aoc_generator(day4)]
-fn parse_input(input: &str) -> Input {
-    let mut lines = input.lines();
-    let numbers = lines
-        .next()
-        .unwrap()
-        .split(',')
-        .map(|x| Value(x.parse().unwrap()))
-        .collect();
-    let boards = lines
-        .chunks(6)
+/// Parse a board from its lines, each given with its line number, checking
+/// that every row is as long as the first.
+fn parse_board(index: usize, lines: &[(usize, &str)]) -> Result<Board> {
+    let rows = lines
+        .iter()
+        .map(|(n, line)| {
+            line.split_whitespace()
+                .map(|value| {
+                    value.parse().map(Value).with_context(|| {
+                        format!(
+                            "line {}: invalid number '{}' on board {}",
+                            n,
+                            value,
+                            index + 1
+                        )
+                    })
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let columns = rows[0].len();
+    if rows.len() > u8::MAX as usize || columns > u8::MAX as usize {
+        bail!(
+            "line {}: board {} is larger than {}x{}",
+            lines[0].0,
+            index + 1,
+            u8::MAX,
+            u8::MAX
+        );
+    }
+    if let Some((i, row)) = rows.iter().find_position(|row| row.len() != columns) {
+        bail!(
+            "line {}: row {} of board {} has {} numbers, expected {}",
+            lines[i].0,
+            i + 1,
+            index + 1,
+            row.len(),
+            columns
+        );
+    }
+    Ok(rows
         .into_iter()
-        .map(|chunk| {
-            chunk
-                .skip(1)
+        .enumerate()
+        .flat_map(|(i, row)| {
+            row.into_iter()
                 .enumerate()
-                .flat_map(move |(row, line)| {
-                    line.split_whitespace()
-                        .enumerate()
-                        .map(move |(column, value)| {
-                            Cell::new(
-                                Row(row as u8),
-                                Column(column as u8),
-                                Value(value.parse().unwrap()),
-                            )
-                        })
-                })
-                .collect()
+                .map(move |(j, value)| Cell::new(Row(i as u8), Column(j as u8), value))
         })
-        .collect();
-    (numbers, boards)
+        .collect())
+}
+
+#[aoc_generator(day4)]
+fn parse_input(input: &str) -> Result<Input> {
+    parse_game(input)
+}
+
+/// Parse the drawn numbers on the first line, followed by boards of any size
+/// separated by blank lines, for use with [`play`], [`simulate`] and
+/// [`validate`].
+pub fn parse_game(input: &str) -> Result<(Vec<Value>, Vec<Board>)> {
+    let mut lines = input.lines().enumerate().map(|(i, line)| (i + 1, line));
+    let numbers = match lines.next() {
+        Some((_, line)) => line
+            .split(',')
+            .map(|x| {
+                x.trim()
+                    .parse()
+                    .map(Value)
+                    .with_context(|| format!("line 1: invalid number '{}'", x))
+            })
+            .collect::<Result<_>>()?,
+        None => bail!("empty input"),
+    };
+    let mut boards = vec![];
+    let mut board_lines = vec![];
+    for (n, line) in lines.chain([(0, "")]) {
+        if !line.trim().is_empty() {
+            board_lines.push((n, line));
+        } else if !board_lines.is_empty() {
+            boards.push(parse_board(boards.len(), &board_lines)?);
+            board_lines.clear();
+        }
+    }
+    Ok((numbers, boards))
+}
+
+/// Something suspicious about a game which parses fine, but likely doesn't
+/// play the way it was meant to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// A value appears more than once on a board, first at `first`.
+    Duplicate {
+        board: usize,
+        row: Row,
+        column: Column,
+        value: Value,
+        first: (Row, Column),
+    },
+    /// A value on a board is never drawn.
+    NeverDrawn {
+        board: usize,
+        row: Row,
+        column: Column,
+        value: Value,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Duplicate {
+                board,
+                row,
+                column,
+                value,
+                first,
+            } => write!(
+                f,
+                "board {}, row {}, column {}: {} already appears at row {}, column {}",
+                board + 1,
+                row.0 + 1,
+                column.0 + 1,
+                value.0,
+                first.0 .0 + 1,
+                first.1 .0 + 1
+            ),
+            Problem::NeverDrawn {
+                board,
+                row,
+                column,
+                value,
+            } => write!(
+                f,
+                "board {}, row {}, column {}: {} is never drawn",
+                board + 1,
+                row.0 + 1,
+                column.0 + 1,
+                value.0
+            ),
+        }
+    }
+}
+
+/// Look for problems with a game, board by board and in reading order.
+///
+/// Ragged boards are already rejected by the generator, so this only checks
+/// the values on each board.
+pub fn validate(numbers: &[Value], boards: &[Board]) -> Vec<Problem> {
+    let mut drawn = [false; 256];
+    numbers.iter().for_each(|v| drawn[v.0 as usize] = true);
+    let mut problems = vec![];
+    for (board, b) in boards.iter().enumerate() {
+        let mut seen: [Option<(Row, Column)>; 256] = [None; 256];
+        for cell in &b.cells {
+            let (row, column, value) = (cell.row, cell.column, cell.value);
+            match seen[value.0 as usize] {
+                Some(first) => problems.push(Problem::Duplicate {
+                    board,
+                    row,
+                    column,
+                    value,
+                    first,
+                }),
+                None => seen[value.0 as usize] = Some((row, column)),
+            }
+            if !drawn[value.0 as usize] {
+                problems.push(Problem::NeverDrawn {
+                    board,
+                    row,
+                    column,
+                    value,
+                });
+            }
+        }
+    }
+    problems
+}
+
+/// Generate a random game: every number from 0 to 99 is drawn in a random order,
+/// and each board holds 25 distinct numbers from that range.
+///
+/// Boards are regenerated until exactly one of them wins last, since otherwise
+/// part 2 has no answer.
+pub fn random_input<R: Rng>(rng: &mut R) -> String {
+    let mut numbers: Vec<Value> = (0..100).map(Value).collect();
+    numbers.shuffle(rng);
+    let n_boards = rng.gen_range(3..=100);
+    let boards: Vec<Board> = loop {
+        let boards: Vec<Board> = (0..n_boards)
+            .map(|_| {
+                let mut values: Vec<u8> = (0..100).collect();
+                values
+                    .partial_shuffle(rng, 25)
+                    .0
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| Cell::new(Row(i as u8 / 5), Column(i as u8 % 5), Value(*v)))
+                    .collect()
+            })
+            .collect();
+        let win_turns: Vec<usize> = boards
+            .iter()
+            .map(|board| {
+                let mut board = board.clone();
+                numbers
+                    .iter()
+                    .position(|value| {
+                        board.draw(*value);
+                        board.check().is_some()
+                    })
+                    .expect("every board to win once all numbers are drawn")
+            })
+            .collect();
+        let last = win_turns.iter().max().unwrap();
+        if win_turns.iter().filter(|turn| *turn == last).count() == 1 {
+            break boards;
+        }
+    };
+    let boards = boards.iter().map(|board| {
+        board
+            .cells
+            .chunks(board.columns as usize)
+            .map(|row| row.iter().map(|c| format!("{:>2}", c.value.0)).join(" "))
+            .join("\n")
+    });
+    format!(
+        "{}\n\n{}",
+        numbers.iter().map(|v| v.0).join(","),
+        boards.format("\n\n")
+    )
+}
+
+/// A board winning the game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Win {
+    /// The index of the board in the input.
+    pub board: usize,
+    /// The number whose draw made the board win.
+    pub draw: Value,
+    /// How many numbers had been drawn when the board won, from 1.
+    pub turn: usize,
+    pub winner: Winner,
+    pub score: usize,
+}
+
+/// Play a full game under the standard rules; see [`play_with`].
+pub fn play(numbers: &[Value], boards: &[Board]) -> Vec<Win> {
+    play_with(numbers, boards, WinPattern::STANDARD)
+}
+
+/// Draw every number until all boards have won, returning their wins in the
+/// order they happened. Boards winning on the same draw are ordered by index,
+/// and boards which never win are left out.
+pub fn play_with(numbers: &[Value], boards: &[Board], patterns: &[WinPattern]) -> Vec<Win> {
+    Game::new(numbers, boards, patterns).collect()
+}
+
+/// A game in progress, yielding wins in the same order as [`play_with`] but
+/// only drawing as many numbers as it needs to.
+pub struct Game<'a> {
+    draws: std::iter::Enumerate<std::slice::Iter<'a, Value>>,
+    patterns: &'a [WinPattern],
+    /// The boards which haven't won yet, with their index in the input.
+    playing: Vec<(usize, Board)>,
+    /// Wins from the last draw which haven't been yielded yet.
+    pending: VecDeque<Win>,
+}
+
+impl<'a> Game<'a> {
+    pub fn new(numbers: &'a [Value], boards: &[Board], patterns: &'a [WinPattern]) -> Self {
+        Self {
+            draws: numbers.iter().enumerate(),
+            patterns,
+            playing: boards.iter().cloned().enumerate().collect(),
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl Iterator for Game<'_> {
+    type Item = Win;
+
+    fn next(&mut self) -> Option<Win> {
+        while self.pending.is_empty() && !self.playing.is_empty() {
+            let (turn, draw) = self.draws.next()?;
+            let (patterns, pending) = (self.patterns, &mut self.pending);
+            self.playing.retain_mut(|(index, board)| {
+                board.draw(*draw);
+                match board.check_with(patterns) {
+                    Some(winner) => {
+                        pending.push_back(Win {
+                            board: *index,
+                            draw: *draw,
+                            turn: turn + 1,
+                            winner,
+                            score: draw.0 as usize * board.sum_of_undrawn_numbers(),
+                        });
+                        false
+                    }
+                    None => true,
+                }
+            });
+        }
+        self.pending.pop_front()
+    }
+}
+
+/// How likely each board is to win first or last when the numbers are drawn
+/// in a random order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Odds {
+    /// The probability of each board winning first, with boards tying for
+    /// first sharing the win equally.
+    pub first: Vec<f64>,
+    /// The probability of each board winning last, shared the same way.
+    pub last: Vec<f64>,
+    /// The expected turn of the first win, from 1, or `None` if no board won
+    /// in any trial.
+    pub expected_first_turn: Option<f64>,
+}
+
+/// Estimate the [`Odds`] of a game by playing it `trials` times, shuffling
+/// the numbers before each one.
+///
+/// Panics if `trials` is 0, since there would be no odds to speak of.
+pub fn simulate<R: Rng>(numbers: &[Value], boards: &[Board], trials: usize, rng: &mut R) -> Odds {
+    assert!(trials > 0, "at least one trial to simulate");
+    let mut first = vec![0.; boards.len()];
+    let mut last = vec![0.; boards.len()];
+    let (mut turns, mut won) = (0, 0);
+    let mut numbers = numbers.to_vec();
+    for _ in 0..trials {
+        numbers.shuffle(rng);
+        let wins = play(&numbers, boards);
+        let (Some(first_win), Some(last_win)) = (wins.first(), wins.last()) else {
+            continue;
+        };
+        for (odds, turn) in [(&mut first, first_win.turn), (&mut last, last_win.turn)] {
+            let tied = wins.iter().filter(|win| win.turn == turn);
+            let share = 1. / tied.clone().count() as f64;
+            tied.for_each(|win| odds[win.board] += share);
+        }
+        turns += first_win.turn;
+        won += 1;
+    }
+    let trials = trials as f64;
+    Odds {
+        first: first.into_iter().map(|n| n / trials).collect(),
+        last: last.into_iter().map(|n| n / trials).collect(),
+        expected_first_turn: (won > 0).then(|| turns as f64 / won as f64),
+    }
 }
 
 #[aoc(day4, part1)]
 fn part1(input: &Input) -> usize {
+    Game::new(&input.0, &input.1, WinPattern::STANDARD)
+        .next()
+        .expect("a board to win before we run out of numbers")
+        .score
+}
+
+#[aoc(day4, part1, Loop)]
+fn part1_loop(input: &Input) -> usize {
     let mut boards: Vec<Board> = input.1.clone();
-    input
-        .0
+    let mut values = input.0.iter();
+    loop {
+        let value = values.next().expect("a board to win");
+        boards.iter_mut().for_each(|board| board.draw(*value));
+        let winning_board = boards.iter().find(|board| board.check().is_some());
+        if let Some(w) = winning_board {
+            return value.0 as usize * w.sum_of_undrawn_numbers();
+        }
+    }
+}
+
+/// When each board wins under the standard rules, without playing the game:
+/// a line is complete on the latest turn of any of its numbers, and a board
+/// wins on the earliest turn any of its lines is complete.
+///
+/// Returns the turn (from 0) and score for each board that ever wins.
+fn win_turns(input: &Input) -> Vec<(usize, usize)> {
+    let (numbers, boards) = input;
+    let mut turn_of = [None; 256];
+    for (turn, value) in numbers.iter().enumerate().rev() {
+        turn_of[value.0 as usize] = Some(turn);
+    }
+    let turn_of = |cell: &Cell| turn_of[cell.value.0 as usize];
+    boards
         .iter()
-        .find_map(|value| {
-            boards.iter_mut().find_map(|board| {
-                board.draw(*value);
-                board
-                    .check()
-                    .is_some()
-                    .then(|| value.0 as usize * board.sum_of_undrawn_numbers())
-            })
+        .filter_map(|board| {
+            // The turn each line is complete on, in a single pass over the
+            // cells. A line with a number that's never drawn is never complete.
+            let mut rows = vec![Some(0); board.rows as usize];
+            let mut columns = vec![Some(0); board.columns as usize];
+            for cell in &board.cells {
+                let turn = turn_of(cell);
+                for line in [
+                    &mut rows[cell.row.0 as usize],
+                    &mut columns[cell.column.0 as usize],
+                ] {
+                    *line = line.zip(turn).map(|(latest, turn)| latest.max(turn));
+                }
+            }
+            let turn = rows.into_iter().chain(columns).flatten().min()?;
+            let undrawn: usize = board
+                .cells
+                .iter()
+                .filter(|cell| turn_of(cell).is_none_or(|t| t > turn))
+                .map(|cell| cell.value.0 as usize)
+                .sum();
+            Some((turn, numbers[turn].0 as usize * undrawn))
         })
+        .collect()
+}
+
+#[aoc(day4, part1, Indexed)]
+fn part1_indexed(input: &Input) -> usize {
+    win_turns(input)
+        .into_iter()
+        .min_by_key(|(turn, _)| *turn)
         .expect("a board to win before we run out of numbers")
-    // let values = input.0.iter();
-    // loop {
-    //     let value = values.next().expect("a board to win");
-    //     boards.iter_mut().for_each(|board| board.draw(*value));
-    //     let winning_board = boards.iter().find(|board| board.check().is_some());
-    //     if let Some(w) = winning_board {
-    //         return value.0 as usize * w.sum_of_undrawn_numbers();
-    //     }
-    // }
+        .1
 }
 
 #[aoc(day4, part2)]
 fn part2(input: &Input) -> usize {
+    play(&input.0, &input.1)
+        .last()
+        .expect("a board to win before we run out of numbers")
+        .score
+}
+
+#[aoc(day4, part2, Loop)]
+fn part2_loop(input: &Input) -> usize {
     let mut boards: Vec<Board> = input.1.clone();
-    input
-        .0
-        .iter()
-        .find_map(|value| {
-            boards.iter_mut().for_each(|board| board.draw(*value));
-            if boards.len() != 1 {
-                boards.retain(|board| board.check().is_none());
-                None
-            } else {
-                boards[0]
-                    .check()
-                    .is_some()
-                    .then(|| value.0 as usize * boards[0].sum_of_undrawn_numbers())
-            }
-        })
+    let mut values = input.0.iter().copied();
+    loop {
+        let value = values.next().expect("a board to win");
+        boards.iter_mut().for_each(|board| board.draw(value));
+        if boards.len() != 1 {
+            boards.retain(|board| board.check().is_none());
+        } else if boards[0].check().is_some() {
+            return value.0 as usize * boards[0].sum_of_undrawn_numbers();
+        }
+    }
+}
+
+#[aoc(day4, part2, Indexed)]
+fn part2_indexed(input: &Input) -> usize {
+    win_turns(input)
+        .into_iter()
+        .max_by_key(|(turn, _)| *turn)
         .expect("a board to win before we run out of numbers")
-    // let mut values = input.0.iter().copied();
-    // loop {
-    //     let value = values.next().expect("a board to win");
-    //     boards.iter_mut().for_each(|board| board.draw(value));
-    //     if boards.len() != 1 {
-    //         boards.retain(|board| !board.check().is_some());
-    //     } else {
-    //         if boards[0].check().is_some() {
-    //             return value.0 as usize * boards[0].sum_of_undrawn_numbers();
-    //         }
-    //     }
-    // }
+        .1
 }
 
 #[cfg(test)]
 mod tests {
 
+    use rand::{rngs::StdRng, SeedableRng};
+
     use super::*;
 
+    const EXAMPLE: &str = "7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1
+
+22 13 17 11  0
+ 8  2 23  4 24
+21  9 14 16  7
+ 6 10  3 18  5
+ 1 12 20 15 19
+
+ 3 15  0  2 22
+ 9 18 13 17  5
+19  8  7 25 23
+20 11 10 24  4
+14 21 16 12  6
+
+14 21 17 24  4
+10 16 15  9 19
+18  8 23 26 20
+22 11 13  6  5
+ 2  0 12  3  7";
+
     #[test]
     fn test_part1() {
         assert_eq!(
-            part1(&parse_input(
-                "7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1
+            part1(
+                &parse_input(
+                    "7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1
 
 22 13 17 11  0
  8  2 23  4 24
@@ -218,7 +706,9 @@ mod tests {
 22 11 13  6  5
  2  0 12  3  7
 "
-            )),
+                )
+                .unwrap()
+            ),
             4512
         );
     }
@@ -226,8 +716,9 @@ mod tests {
     #[test]
     fn test_part2() {
         assert_eq!(
-            part2(&parse_input(
-                "7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1
+            part2(
+                &parse_input(
+                    "7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1
 
     22 13 17 11  0
      8  2 23  4 24
@@ -246,8 +737,248 @@ mod tests {
     18  8 23 26 20
     22 11 13  6  5
      2  0 12  3  7"
-            )),
+                )
+                .unwrap()
+            ),
             1924
         );
     }
+
+    #[test]
+    fn test_part1_loop() {
+        assert_eq!(part1_loop(&parse_input(EXAMPLE).unwrap()), 4512);
+    }
+
+    #[test]
+    fn test_part2_loop() {
+        assert_eq!(part2_loop(&parse_input(EXAMPLE).unwrap()), 1924);
+    }
+
+    #[test]
+    fn test_indexed() {
+        let input = parse_input(EXAMPLE).unwrap();
+        assert_eq!(win_turns(&input), vec![(13, 2192), (14, 1924), (11, 4512)]);
+        assert_eq!(part1_indexed(&input), 4512);
+        assert_eq!(part2_indexed(&input), 1924);
+    }
+
+    #[test]
+    fn test_board_sizes() {
+        let input = parse_input(
+            "5,1,3,9,2,6
+
+1 2 3
+4 5 6
+
+7 8
+9 1
+2 3",
+        )
+        .unwrap();
+        assert_eq!(
+            input
+                .1
+                .iter()
+                .map(|b| (b.rows, b.columns))
+                .collect::<Vec<_>>(),
+            vec![(2, 3), (3, 2)]
+        );
+        // The second board wins first with its middle row, then the first
+        // board with its top row.
+        assert_eq!(part1(&input), 9 * (7 + 8 + 2));
+        assert_eq!(part2(&input), 2 * (4 + 6));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_input("1,2\n\n1 2\n3\n").unwrap_err().to_string(),
+            "line 4: row 2 of board 1 has 1 numbers, expected 2"
+        );
+        assert_eq!(
+            parse_input("1,2\n\n1 2\n3 4\n\n5 x")
+                .unwrap_err()
+                .to_string(),
+            "line 6: invalid number 'x' on board 2"
+        );
+    }
+
+    #[test]
+    fn test_win_patterns() {
+        let (numbers, boards) = parse_input(EXAMPLE).unwrap();
+        let check_after = |drawn: &[u8], patterns: &[WinPattern]| {
+            let mut board = boards[0].clone();
+            drawn.iter().for_each(|v| board.draw(Value(*v)));
+            board.check_with(patterns)
+        };
+        assert_eq!(
+            check_after(&[8, 2, 23, 4, 24], WinPattern::STANDARD),
+            Some(Winner::Row(Row(1)))
+        );
+        assert_eq!(
+            check_after(&[13, 2, 9, 10, 12], WinPattern::STANDARD),
+            Some(Winner::Column(Column(1)))
+        );
+
+        let diagonals = [WinPattern::Diagonals];
+        assert_eq!(
+            check_after(&[22, 2, 14, 18, 19], &diagonals),
+            Some(Winner::Diagonal)
+        );
+        assert_eq!(
+            check_after(&[0, 4, 14, 10, 1], &diagonals),
+            Some(Winner::AntiDiagonal)
+        );
+        assert_eq!(check_after(&[22, 2, 14, 18], &diagonals), None);
+        assert_eq!(
+            check_after(&[22, 2, 14, 18, 19], WinPattern::STANDARD),
+            None
+        );
+
+        assert_eq!(
+            check_after(&[22, 0, 1, 19], &[WinPattern::Corners]),
+            Some(Winner::Corners)
+        );
+        assert_eq!(
+            check_after(
+                &numbers.iter().map(|v| v.0).collect::<Vec<_>>(),
+                &[WinPattern::Blackout]
+            ),
+            Some(Winner::Blackout)
+        );
+        assert_eq!(check_after(&[22, 0, 1], &[WinPattern::Blackout]), None);
+
+        // A small X in the top left corner.
+        let mask = WinPattern::Mask(vec![
+            (Row(0), Column(0)),
+            (Row(0), Column(2)),
+            (Row(1), Column(1)),
+            (Row(2), Column(0)),
+            (Row(2), Column(2)),
+        ]);
+        let patterns = [WinPattern::Corners, mask];
+        assert_eq!(
+            check_after(&[22, 17, 2, 21, 14], &patterns),
+            Some(Winner::Mask(1))
+        );
+        assert_eq!(check_after(&[22, 17, 2, 21], &patterns), None);
+        let outside = WinPattern::Mask(vec![(Row(0), Column(0)), (Row(5), Column(0))]);
+        assert_eq!(
+            check_after(&numbers.iter().map(|v| v.0).collect::<Vec<_>>(), &[outside]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_play() {
+        let (numbers, boards) = parse_input(EXAMPLE).unwrap();
+        let wins = play(&numbers, &boards);
+        assert_eq!(
+            wins,
+            vec![
+                Win {
+                    board: 2,
+                    draw: Value(24),
+                    turn: 12,
+                    winner: Winner::Row(Row(0)),
+                    score: 4512,
+                },
+                Win {
+                    board: 0,
+                    draw: Value(16),
+                    turn: 14,
+                    winner: Winner::Row(Row(2)),
+                    score: 137 * 16,
+                },
+                Win {
+                    board: 1,
+                    draw: Value(13),
+                    turn: 15,
+                    winner: Winner::Column(Column(2)),
+                    score: 1924,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_game_stops_early() {
+        let (numbers, boards) = parse_input(EXAMPLE).unwrap();
+        let mut game = Game::new(&numbers, &boards, WinPattern::STANDARD);
+        assert_eq!(game.next().map(|win| win.turn), Some(12));
+        assert_eq!(game.draws.len(), numbers.len() - 12);
+        assert_eq!(game.map(|win| win.board).collect::<Vec<_>>(), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_empty_board() {
+        let board: Board = std::iter::empty().collect();
+        assert_eq!(
+            board.check_with(&[WinPattern::Diagonals, WinPattern::Corners]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_simulate() {
+        let (numbers, boards) = parse_game("1,2,3\n\n1\n\n2 3").unwrap();
+        let odds = simulate(&numbers, &boards, 10_000, &mut StdRng::seed_from_u64(2021));
+        // Each column of the second board wins on its own, so the first
+        // board only wins first if 1 is drawn first.
+        assert!((odds.first[0] - 1. / 3.).abs() < 0.02, "{:?}", odds);
+        assert!((odds.last[0] - 2. / 3.).abs() < 0.02, "{:?}", odds);
+        assert!((odds.first[0] + odds.first[1] - 1.).abs() < 1e-9);
+        assert_eq!(odds.expected_first_turn, Some(1.));
+
+        // Both boards always win together with their top row, so share
+        // every win.
+        let (numbers, boards) = parse_input("1,2\n\n1 2\n3 4\n\n2 1\n4 3").unwrap();
+        let odds = simulate(&numbers, &boards, 100, &mut StdRng::seed_from_u64(2021));
+        assert_eq!(odds.first, vec![0.5, 0.5]);
+        assert_eq!(odds.last, vec![0.5, 0.5]);
+        assert_eq!(odds.expected_first_turn, Some(2.));
+
+        let odds = simulate(
+            &numbers[..1],
+            &boards,
+            100,
+            &mut StdRng::seed_from_u64(2021),
+        );
+        assert_eq!(odds.expected_first_turn, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one trial")]
+    fn test_simulate_no_trials() {
+        let (numbers, boards) = parse_input(EXAMPLE).unwrap();
+        simulate(&numbers, &boards, 0, &mut StdRng::seed_from_u64(2021));
+    }
+
+    #[test]
+    fn test_validate() {
+        let (numbers, boards) = parse_input(EXAMPLE).unwrap();
+        assert_eq!(validate(&numbers, &boards), vec![]);
+
+        let (numbers, boards) = parse_input("1,2,3,4\n\n1 2\n3 4\n\n4 5\n1 4").unwrap();
+        let problems = validate(&numbers, &boards);
+        assert_eq!(
+            problems.iter().map(|p| p.to_string()).collect::<Vec<_>>(),
+            vec![
+                "board 2, row 1, column 2: 5 is never drawn",
+                "board 2, row 2, column 2: 4 already appears at row 1, column 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_draw_marks_duplicates() {
+        let (_, boards) = parse_input("1\n\n1 2\n3 1").unwrap();
+        let mut board = boards[0].clone();
+        board.draw(Value(1));
+        assert_eq!(board.sum_of_undrawn_numbers(), 5);
+        assert_eq!(
+            board.check_with(&[WinPattern::Diagonals]),
+            Some(Winner::Diagonal)
+        );
+    }
 }
@@ -1,24 +1,300 @@
+use std::{cmp::Reverse, fs, ops::Range, path::Path};
+
+use anyhow::{Context, Result};
 use aoc_runner_derive::{aoc, aoc_generator};
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use rand::Rng;
 use serde::Deserialize;
 
-fn find_n_duplicates<T>(iter: T) -> usize
-where
-    T: Iterator<Item = Point>,
-{
-    let mut counts = vec![0; 1000 * 1000];
-    let mut overlaps = 0;
-    iter.for_each(|point| {
-        let pos = 1000 * point.x + point.y;
-        if counts[pos] == 1 {
-            overlaps += 1;
+/// Use a dense grid as long as it has at most this many cells per point on
+/// the vents; past that, most of it would be empty so a map is smaller.
+const DENSE_CELLS_PER_POINT: usize = 16;
+
+/// How many vents cover each point of the grid, which only spans the
+/// bounding box of the vents.
+#[derive(Clone, Debug)]
+pub struct Heatmap {
+    extent: Viewport,
+    counts: Counts,
+}
+
+#[derive(Clone, Debug)]
+enum Counts {
+    /// Indexed by `height * (x - x0) + (y - y0)`, relative to the extent.
+    Dense(Vec<u32>),
+    /// Only the points covered by a vent.
+    Sparse(HashMap<Point, u32>),
+}
+
+/// A rectangle of the grid, with exclusive upper bounds.
+///
+/// Bounds are wider than coordinates, so that a viewport can end just past
+/// `i32::MAX`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: Range<i64>,
+    pub y: Range<i64>,
+}
+
+impl Viewport {
+    /// The smallest viewport holding every point, or an empty one at the
+    /// origin if there are none.
+    pub fn around<T>(points: T) -> Self
+    where
+        T: IntoIterator<Item = Point>,
+    {
+        points
+            .into_iter()
+            .fold(None, |viewport: Option<Self>, p| {
+                let (px, py) = (p.x as i64, p.y as i64);
+                Some(match viewport {
+                    None => Self {
+                        x: px..px + 1,
+                        y: py..py + 1,
+                    },
+                    Some(Self { x, y }) => Self {
+                        x: x.start.min(px)..x.end.max(px + 1),
+                        y: y.start.min(py)..y.end.max(py + 1),
+                    },
+                })
+            })
+            .unwrap_or(Self { x: 0..0, y: 0..0 })
+    }
+
+    fn width(&self) -> usize {
+        (self.x.end - self.x.start).max(0) as usize
+    }
+
+    fn height(&self) -> usize {
+        (self.y.end - self.y.start).max(0) as usize
+    }
+
+    /// Where the point is within a grid covering the viewport, if it's in it.
+    fn index(&self, point: Point) -> Option<usize> {
+        let (x, y) = (point.x as i64, point.y as i64);
+        (self.x.contains(&x) && self.y.contains(&y))
+            .then(|| self.height() * (x - self.x.start) as usize + (y - self.y.start) as usize)
+    }
+}
+
+impl Heatmap {
+    /// Count the points, choosing a dense grid or a sparse map depending on
+    /// how much of the extent `length` points would cover.
+    fn from_points<T>(extent: Viewport, length: usize, iter: T) -> Self
+    where
+        T: Iterator<Item = Point>,
+    {
+        let area = extent.width().saturating_mul(extent.height());
+        if area <= length.saturating_mul(DENSE_CELLS_PER_POINT) {
+            Self::dense(extent, iter)
+        } else {
+            Self::sparse(extent, iter)
+        }
+    }
+
+    fn dense<T>(extent: Viewport, iter: T) -> Self
+    where
+        T: Iterator<Item = Point>,
+    {
+        let mut counts = vec![0; extent.width() * extent.height()];
+        iter.for_each(|point| {
+            counts[extent.index(point).expect("points to be within the extent")] += 1
+        });
+        Self {
+            extent,
+            counts: Counts::Dense(counts),
+        }
+    }
+
+    fn sparse<T>(extent: Viewport, iter: T) -> Self
+    where
+        T: Iterator<Item = Point>,
+    {
+        let mut counts = HashMap::new();
+        iter.for_each(|point| *counts.entry(point).or_default() += 1);
+        Self {
+            extent,
+            counts: Counts::Sparse(counts),
+        }
+    }
+
+    pub fn is_sparse(&self) -> bool {
+        matches!(self.counts, Counts::Sparse(_))
+    }
+
+    /// The number of vents covering a point, which is 0 outside the extent.
+    pub fn count(&self, point: Point) -> u32 {
+        match &self.counts {
+            Counts::Dense(counts) => self.extent.index(point).map_or(0, |i| counts[i]),
+            Counts::Sparse(counts) => counts.get(&point).copied().unwrap_or(0),
+        }
+    }
+
+    /// The number of points covered by at least two vents.
+    pub fn overlaps(&self) -> usize {
+        match &self.counts {
+            Counts::Dense(counts) => counts.iter().filter(|c| **c > 1).count(),
+            Counts::Sparse(counts) => counts.values().filter(|c| **c > 1).count(),
+        }
+    }
+
+    /// Every point covered by a vent, with the number of vents covering it,
+    /// in no particular order.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (Point, u32)> + '_> {
+        match &self.counts {
+            Counts::Dense(counts) => {
+                let (x0, y0, height) = (
+                    self.extent.x.start,
+                    self.extent.y.start,
+                    self.extent.height(),
+                );
+                Box::new(
+                    counts
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, c)| **c > 0)
+                        .map(move |(i, c)| {
+                            // Within the extent, so always a valid coordinate.
+                            let (x, y) = (x0 + (i / height) as i64, y0 + (i % height) as i64);
+                            let point = Point::new(x as i32, y as i32);
+                            (point, *c)
+                        }),
+                )
+            }
+            Counts::Sparse(counts) => Box::new(counts.iter().map(|(point, c)| (*point, *c))),
+        }
+    }
+
+    /// The smallest viewport holding every point covered by a vent, or
+    /// `None` if there are no vents at all.
+    pub fn bounds(&self) -> Option<Viewport> {
+        let viewport = Viewport::around(self.iter().map(|(point, _)| point));
+        (!viewport.x.is_empty()).then_some(viewport)
+    }
+
+    fn rows<'a>(&'a self, viewport: &'a Viewport) -> impl Iterator<Item = Vec<u32>> + 'a {
+        viewport.y.clone().map(move |y| {
+            viewport
+                .x
+                .clone()
+                .map(|x| match (i32::try_from(x), i32::try_from(y)) {
+                    (Ok(x), Ok(y)) => self.count(Point::new(x, y)),
+                    _ => 0,
+                })
+                .collect()
+        })
+    }
+
+    /// Render the viewport like the puzzle does, with `.` for no vents, the
+    /// count of vents otherwise, and `#` when there are more than 9.
+    pub fn to_ascii(&self, viewport: &Viewport) -> String {
+        self.rows(viewport)
+            .map(|row| {
+                row.into_iter()
+                    .map(|count| match count {
+                        0 => '.',
+                        1..=9 => char::from_digit(count, 10).unwrap(),
+                        _ => '#',
+                    })
+                    .collect::<String>()
+            })
+            .join("\n")
+    }
+
+    /// Render the viewport as a binary PGM image, one pixel per point, where
+    /// white is the highest count in the viewport.
+    pub fn to_pgm(&self, viewport: &Viewport) -> Vec<u8> {
+        let rows: Vec<Vec<u32>> = self.rows(viewport).collect();
+        let max = rows.iter().flatten().copied().max().unwrap_or(0).max(1);
+        let mut pgm = format!("P5\n{} {}\n255\n", viewport.width(), viewport.height()).into_bytes();
+        pgm.extend(rows.iter().flatten().map(|count| (count * 255 / max) as u8));
+        pgm
+    }
+
+    pub fn write_pgm(&self, path: impl AsRef<Path>, viewport: &Viewport) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_pgm(viewport))
+            .with_context(|| format!("writing heatmap {}", path.display()))
+    }
+}
+
+/// Count the points covered by the vents, optionally including diagonal ones.
+pub fn heatmap(input: &[Vector], diagonals: bool) -> Heatmap {
+    Heatmap::from_points(
+        Viewport::around(input.iter().flat_map(|v| [v.a, v.b])),
+        input
+            .iter()
+            .filter(|v| v.is_counted(diagonals))
+            .map(Vector::len)
+            .sum(),
+        input
+            .iter()
+            .filter_map(|v| v.counted_points(diagonals))
+            .flatten(),
+    )
+}
+
+/// The vents along with their heatmap, to find out where and why they
+/// overlap.
+pub struct VentMap<'a> {
+    vectors: &'a [Vector],
+    diagonals: bool,
+    heatmap: Heatmap,
+}
+
+impl<'a> VentMap<'a> {
+    pub fn new(vectors: &'a [Vector], diagonals: bool) -> Self {
+        Self {
+            vectors,
+            diagonals,
+            heatmap: heatmap(vectors, diagonals),
         }
-        counts[pos] += 1;
-    });
-    overlaps
+    }
+
+    pub fn heatmap(&self) -> &Heatmap {
+        &self.heatmap
+    }
+
+    /// The `k` points covered by the most vents, most first, breaking ties
+    /// by position.
+    pub fn hottest(&self, k: usize) -> Vec<(Point, u32)> {
+        self.heatmap
+            .iter()
+            .sorted_by_key(|(point, count)| (Reverse(*count), *point))
+            .take(k)
+            .collect()
+    }
+
+    /// The counted vectors passing through a point, in input order.
+    pub fn through(&self, point: Point) -> Vec<&'a Vector> {
+        self.vectors
+            .iter()
+            .filter(|v| {
+                v.counted_points(self.diagonals)
+                    .is_some_and(|mut points| points.any(|p| p == point))
+            })
+            .collect()
+    }
+
+    /// For each vector, in input order, the points where it overlaps with
+    /// another vector. Vectors that aren't counted have none.
+    pub fn overlaps_per_line(&self) -> Vec<HashSet<Point>> {
+        self.vectors
+            .iter()
+            .map(|v| {
+                v.counted_points(self.diagonals)
+                    .into_iter()
+                    .flatten()
+                    .filter(|p| self.heatmap.count(*p) > 1)
+                    .collect()
+            })
+            .collect()
+    }
 }
 
 /// Annoying workaround for ranges not going backwards...
-fn range_inclusive(a: usize, b: usize) -> Box<dyn Iterator<Item = usize>> {
+fn range_inclusive(a: i32, b: i32) -> Box<dyn Iterator<Item = i32>> {
     if b > a {
         Box::new(a..=b)
     } else {
@@ -27,21 +303,21 @@ fn range_inclusive(a: usize, b: usize) -> Box<dyn Iterator<Item = usize>> {
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
-struct Point {
-    x: usize,
-    y: usize,
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
 }
 
 impl Point {
-    fn new(x: usize, y: usize) -> Self {
+    pub fn new(x: i32, y: i32) -> Self {
         Self { x, y }
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
-struct Vector {
-    a: Point,
-    b: Point,
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct Vector {
+    pub a: Point,
+    pub b: Point,
 }
 
 impl Vector {
@@ -61,9 +337,30 @@ impl Vector {
         }
     }
 
+    /// Whether the vector is straight, or diagonal when those count too.
+    fn is_counted(&self, diagonals: bool) -> bool {
+        self.is_horizontal() || self.is_vertical() || (diagonals && self.is_diagonal())
+    }
+
+    /// The points covered by the vector, if it's counted. A single point is
+    /// both straight and diagonal, but it's only covered once.
+    fn counted_points(&self, diagonals: bool) -> Option<Box<dyn Iterator<Item = Point> + '_>> {
+        if self.is_horizontal() || self.is_vertical() {
+            Some(self.points())
+        } else if diagonals && self.is_diagonal() {
+            Some(self.diagonal_points())
+        } else {
+            None
+        }
+    }
+
+    /// The number of points on a straight or diagonal vector.
+    fn len(&self) -> usize {
+        self.a.x.abs_diff(self.b.x).max(self.a.y.abs_diff(self.b.y)) as usize + 1
+    }
+
     fn is_diagonal(&self) -> bool {
-        (self.a.x as isize - self.b.x as isize).abs()
-            == (self.a.y as isize - self.b.y as isize).abs()
+        self.a.x.abs_diff(self.b.x) == self.a.y.abs_diff(self.b.y)
     }
 
     fn diagonal_points(&self) -> Box<dyn Iterator<Item = Point> + '_> {
@@ -85,30 +382,68 @@ fn parse_input(input: &str) -> Input {
         .collect()
 }
 
+/// Generate random vents: mostly horizontal, vertical and 45° diagonal lines,
+/// all within a 1000x1000 grid like the real inputs.
+///
+/// Real inputs don't have lines at any other angle, but a few are thrown in
+/// so that solutions are checked to ignore them.
+pub fn random_input<R: Rng>(rng: &mut R) -> String {
+    (0..500)
+        .map(|_| loop {
+            let (x1, y1): (isize, isize) = (rng.gen_range(0..1000), rng.gen_range(0..1000));
+            let (x2, y2) = match rng.gen_range(0..10) {
+                0..=2 => (x1, rng.gen_range(0..1000)),
+                3..=5 => (rng.gen_range(0..1000), y1),
+                6 => (rng.gen_range(0..1000), rng.gen_range(0..1000)),
+                _ => {
+                    let x2 = rng.gen_range(0..1000);
+                    let dy = if rng.gen() { x2 - x1 } else { x1 - x2 };
+                    (x2, y1 + dy)
+                }
+            };
+            if (x1, y1) != (x2, y2) && (0..1000).contains(&y2) {
+                break format!("{},{} -> {},{}", x1, y1, x2, y2);
+            }
+        })
+        .join("\n")
+}
+
 #[aoc(day5, part1)]
 fn part1(input: &Input) -> usize {
-    find_n_duplicates(
-        input
-            .iter()
-            .filter(|v| v.is_horizontal() | v.is_vertical())
-            .flat_map(|v| v.points()),
-    )
+    heatmap(input, false).overlaps()
 }
 
 #[aoc(day5, part2)]
 fn part2(input: &Input) -> usize {
-    find_n_duplicates(
-        input
-            .iter()
-            .filter(|v| v.is_horizontal() || v.is_vertical())
-            .flat_map(|v| v.points())
-            .chain(
-                input
-                    .iter()
-                    .filter(|v| v.is_diagonal())
-                    .flat_map(|v| v.diagonal_points()),
-            ),
-    )
+    heatmap(input, true).overlaps()
+}
+
+/// A deliberately simple count of overlaps straight from the endpoints, to
+/// check the other solutions against.
+fn naive_overlaps(input: &Input, diagonals: bool) -> usize {
+    let mut counts: HashMap<(i32, i32), usize> = HashMap::new();
+    for v in input {
+        let (dx, dy) = (v.b.x - v.a.x, v.b.y - v.a.y);
+        if !(dx == 0 || dy == 0 || (diagonals && dx.abs() == dy.abs())) {
+            continue;
+        }
+        for step in 0..=dx.abs().max(dy.abs()) {
+            *counts
+                .entry((v.a.x + step * dx.signum(), v.a.y + step * dy.signum()))
+                .or_default() += 1;
+        }
+    }
+    counts.values().filter(|count| **count > 1).count()
+}
+
+#[aoc(day5, part1, Naive)]
+fn part1_naive(input: &Input) -> usize {
+    naive_overlaps(input, false)
+}
+
+#[aoc(day5, part2, Naive)]
+fn part2_naive(input: &Input) -> usize {
+    naive_overlaps(input, true)
 }
 
 #[cfg(test)]
@@ -116,6 +451,17 @@ mod tests {
 
     use super::*;
 
+    const EXAMPLE: &str = "0,9 -> 5,9
+8,0 -> 0,8
+9,4 -> 3,4
+2,2 -> 2,1
+7,0 -> 7,4
+6,4 -> 2,0
+0,9 -> 2,9
+3,4 -> 1,4
+0,0 -> 8,8
+5,5 -> 8,2";
+
     #[test]
     fn test_part1() {
         assert_eq!(
@@ -153,4 +499,202 @@ mod tests {
             12
         );
     }
+
+    #[test]
+    fn test_naive() {
+        let input = parse_input(&format!("{}\n0,0 -> 9,3\n1,0 -> 3,7", EXAMPLE));
+        assert_eq!(part1_naive(&input), 5);
+        assert_eq!(part2_naive(&input), 12);
+        assert_eq!(part1(&input), 5);
+        assert_eq!(part2(&input), 12);
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let input = parse_input(EXAMPLE);
+        let map = heatmap(&input, true);
+        let bounds = map.bounds().unwrap();
+        assert_eq!(bounds, Viewport { x: 0..10, y: 0..10 });
+        assert_eq!(
+            map.to_ascii(&bounds),
+            "1.1....11.
+.111...2..
+..2.1.111.
+...1.2.2..
+.112313211
+...1.2....
+..1...1...
+.1.....1..
+1.......1.
+222111...."
+        );
+        assert_eq!(
+            heatmap(&input, false).to_ascii(&Viewport { x: 1..4, y: 3..5 }),
+            "...\n112"
+        );
+
+        let pgm = map.to_pgm(&Viewport { x: 0..3, y: 0..1 });
+        assert_eq!(pgm, b"P5\n3 1\n255\n\xff\x00\xff");
+    }
+
+    #[test]
+    fn test_negative_coordinates() {
+        let input = parse_input(
+            "-3,0 -> 3,0
+0,-3 -> 0,3
+-2,-2 -> 2,2
+2,-2 -> -2,2",
+        );
+        let map = heatmap(&input, true);
+        assert_eq!(map.bounds().unwrap(), Viewport { x: -3..4, y: -3..4 });
+        assert_eq!(map.count(Point::new(0, 0)), 4);
+        assert_eq!(map.count(Point::new(-5, 0)), 0);
+        assert_eq!(
+            map.to_ascii(&Viewport { x: -3..4, y: -3..4 }),
+            "...1...
+.1.1.1.
+..111..
+1114111
+..111..
+.1.1.1.
+...1..."
+        );
+        assert_eq!(part1(&input), 1);
+        assert_eq!(part2(&input), 1);
+    }
+
+    #[test]
+    fn test_extreme_coordinates() {
+        let input = parse_input("2147483647,0 -> 2147483647,2\n-2147483648,1 -> -2147483646,1");
+        let map = heatmap(&input, true);
+        assert!(map.is_sparse());
+        assert_eq!(map.count(Point::new(i32::MAX, 2)), 1);
+        assert_eq!(
+            map.bounds().unwrap(),
+            Viewport {
+                x: i32::MIN as i64..i32::MAX as i64 + 1,
+                y: 0..3
+            }
+        );
+        let edge = Viewport {
+            x: i32::MAX as i64..i32::MAX as i64 + 2,
+            y: 0..1,
+        };
+        assert_eq!(map.to_ascii(&edge), "1.");
+
+        let input = parse_input("2147483647,0 -> 2147483647,2\n2147483646,1 -> 2147483647,1");
+        let map = heatmap(&input, true);
+        assert!(!map.is_sparse());
+        assert_eq!(map.overlaps(), 1);
+    }
+
+    #[test]
+    fn test_single_point() {
+        let input = parse_input("5,5 -> 5,5\n0,0 -> 3,0");
+        assert_eq!(part1(&input), 0);
+        assert_eq!(part2(&input), 0);
+        assert_eq!(heatmap(&input, true).count(Point::new(5, 5)), 1);
+    }
+
+    #[test]
+    fn test_sparse() {
+        let input = parse_input(
+            "-100000,0 -> 100000,0
+0,-100000 -> 0,100000
+5,5 -> 5,5",
+        );
+        let map = heatmap(&input, true);
+        assert!(map.is_sparse());
+        assert_eq!(map.count(Point::new(0, 0)), 2);
+        assert_eq!(map.count(Point::new(5, 5)), 1);
+        assert_eq!(map.overlaps(), 1);
+        assert_eq!(
+            map.bounds().unwrap(),
+            Viewport {
+                x: -100000..100001,
+                y: -100000..100001
+            }
+        );
+
+        let input = parse_input(EXAMPLE);
+        let extent = Viewport::around(input.iter().flat_map(|v| [v.a, v.b]));
+        let points = || {
+            input
+                .iter()
+                .filter(|v| v.is_horizontal() || v.is_vertical())
+        };
+        let dense = Heatmap::dense(extent.clone(), points().flat_map(|v| v.points()));
+        let sparse = Heatmap::sparse(extent, points().flat_map(|v| v.points()));
+        assert!(!heatmap(&input, false).is_sparse());
+        assert_eq!(dense.overlaps(), sparse.overlaps());
+        assert_eq!(dense.bounds(), sparse.bounds());
+        let bounds = dense.bounds().unwrap();
+        assert_eq!(dense.to_ascii(&bounds), sparse.to_ascii(&bounds));
+    }
+
+    #[test]
+    fn test_vent_map_single_point() {
+        let input = parse_input("5,5 -> 5,5\n0,0 -> 3,0\n3,3 -> 6,6");
+        let map = VentMap::new(&input, true);
+        assert_eq!(map.heatmap().count(Point::new(5, 5)), 2);
+        assert_eq!(map.through(Point::new(5, 5)), vec![&input[0], &input[2]]);
+        assert_eq!(
+            map.overlaps_per_line(),
+            vec![
+                [Point::new(5, 5)].into_iter().collect(),
+                HashSet::new(),
+                [Point::new(5, 5)].into_iter().collect(),
+            ]
+        );
+
+        let map = VentMap::new(&input, false);
+        assert_eq!(map.heatmap().count(Point::new(5, 5)), 1);
+        assert_eq!(map.through(Point::new(5, 5)), vec![&input[0]]);
+        assert!(map
+            .overlaps_per_line()
+            .iter()
+            .all(|points| points.is_empty()));
+    }
+
+    #[test]
+    fn test_vent_map() {
+        let input = parse_input(EXAMPLE);
+        let map = VentMap::new(&input, true);
+        assert_eq!(
+            map.hottest(3),
+            vec![
+                (Point::new(4, 4), 3),
+                (Point::new(6, 4), 3),
+                (Point::new(0, 9), 2)
+            ]
+        );
+        assert_eq!(map.hottest(100).len(), 39);
+
+        let through = map.through(Point::new(4, 4));
+        assert_eq!(
+            through,
+            vec![&input[1], &input[2], &input[8]],
+            "{:?}",
+            through
+        );
+        assert!(map.through(Point::new(9, 0)).is_empty());
+        // Diagonals aren't counted without asking for them.
+        assert_eq!(
+            VentMap::new(&input, false).through(Point::new(4, 4)),
+            vec![&input[2]]
+        );
+
+        let overlaps = map.overlaps_per_line();
+        assert_eq!(overlaps.len(), input.len());
+        assert_eq!(
+            overlaps[0],
+            [Point::new(0, 9), Point::new(1, 9), Point::new(2, 9)]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(
+            overlaps.iter().flatten().collect::<HashSet<_>>().len(),
+            part2(&input)
+        );
+    }
 }
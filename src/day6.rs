@@ -1,6 +1,8 @@
 use anyhow::Result;
 use aoc_runner_derive::{aoc, aoc_generator};
 use hashbrown::hash_map::HashMap;
+use itertools::Itertools;
+use rand::Rng;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 struct Fish(u8);
@@ -18,6 +20,11 @@ fn parse_input(input: &str) -> Input {
         })
 }
 
+/// Generate a random school of fish, with timers in the same range as real inputs.
+pub fn random_input<R: Rng>(rng: &mut R) -> String {
+    (0..300).map(|_| rng.gen_range(1..=5)).join(",")
+}
+
 fn evolve(fish: &Input) -> Input {
     fish.iter()
         .flat_map(|(k, v)| -> Vec<(Fish, usize)> {
@@ -1,4 +1,6 @@
 use aoc_runner_derive::{aoc, aoc_generator};
+use itertools::Itertools;
+use rand::Rng;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct Crab(u16);
@@ -10,6 +12,11 @@ fn parse_input(input: &str) -> Input {
     input.split(',').map(|i| Crab(i.parse().unwrap())).collect()
 }
 
+/// Generate random horizontal positions for a swarm of crabs.
+pub fn random_input<R: Rng>(rng: &mut R) -> String {
+    (0..1000).map(|_| rng.gen_range(0..2000)).join(",")
+}
+
 fn best_position_part1(crabs: &Input) -> u16 {
     let mut crabs = crabs.clone();
     crabs.sort();
@@ -24,7 +31,7 @@ fn best_position_part2(crabs: &Input) -> (u16, u16) {
 fn cost_part1(crabs: &Input, position: u16) -> usize {
     crabs
         .iter()
-        .map(|crab| (crab.0 as isize - position as isize).abs() as usize)
+        .map(|crab| crab.0.abs_diff(position) as usize)
         .sum()
 }
 
@@ -35,7 +42,7 @@ fn sum_1_to_n(n: usize) -> usize {
 fn cost_part2(crabs: &Input, position: u16) -> usize {
     crabs
         .iter()
-        .map(|crab| sum_1_to_n((crab.0 as isize - position as isize).abs() as usize))
+        .map(|crab| sum_1_to_n(crab.0.abs_diff(position) as usize))
         .sum()
 }
 
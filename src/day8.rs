@@ -24,6 +24,7 @@ use std::{collections::HashMap, ops::Deref};
 
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
+use rand::{seq::SliceRandom, Rng};
 
 trait IterExt: Iterator + Sized {
     fn find_only<P>(&mut self, predicate: P) -> Option<Self::Item>
@@ -71,7 +72,7 @@ impl Deref for Pattern {
     type Target = [char];
 
     fn deref(&self) -> &Self::Target {
-        &*self.0
+        &self.0
     }
 }
 
@@ -105,7 +106,8 @@ impl Entry {
     fn letters_with_count(&self, n: usize) -> impl Iterator<Item = char> + '_ {
         self.letter_counts
             .iter()
-            .filter_map(move |(k, v)| (*v == n).then(|| *k))
+            .filter(move |(_, v)| **v == n)
+            .map(|(k, _)| *k)
     }
 
     fn letter_with_count(&self, n: usize) -> char {
@@ -265,6 +267,37 @@ fn parse_input(input: &str) -> Input {
     input.lines().map(parse_line).collect()
 }
 
+/// The segments lit for each digit on a correctly wired display.
+const DIGITS: [&str; 10] = [
+    "abcefg", "cf", "acdeg", "acdfg", "bcdf", "abdfg", "abdefg", "acf", "abcdefg", "abcdfg",
+];
+
+/// Generate random entries, each using its own shuffled wiring and with the
+/// patterns and their segments in random order.
+pub fn random_input<R: Rng>(rng: &mut R) -> String {
+    (0..200)
+        .map(|_| {
+            let mut wiring: Vec<char> = ('a'..='g').collect();
+            wiring.shuffle(rng);
+            let scramble = |digit: usize, rng: &mut R| {
+                let mut segments: Vec<char> = DIGITS[digit]
+                    .chars()
+                    .map(|c| wiring[c as usize - 'a' as usize])
+                    .collect();
+                segments.shuffle(rng);
+                segments.into_iter().collect::<String>()
+            };
+            let mut digits: Vec<usize> = (0..10).collect();
+            digits.shuffle(rng);
+            let patterns = digits.iter().map(|d| scramble(*d, rng)).join(" ");
+            let output = (0..4)
+                .map(|_| scramble(rng.gen_range(0..10), rng))
+                .join(" ");
+            format!("{} | {}", patterns, output)
+        })
+        .join("\n")
+}
+
 #[aoc(day8, part1)]
 fn part1(input: &Input) -> usize {
     input
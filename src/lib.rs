@@ -11,4 +11,7 @@ pub mod day8;
 // pub mod day9;
 // pub mod day10;
 
+pub mod solutions;
+pub mod store;
+
 aoc_lib! { year = 2021 }
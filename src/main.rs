@@ -1,7 +1,288 @@
-use aoc_runner_derive::aoc_main;
+use std::{env, process::ExitCode, time::Duration};
+
+use anyhow::{bail, Result};
+use hashbrown::HashMap;
 use mimalloc::MiMalloc;
+use serde::Serialize;
+
+use aoc_2021::{
+    solutions::{self, Run, Solution},
+    store::{Store, Verdict},
+    YEAR,
+};
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
-aoc_main! { lib = aoc_2021 }
+const USAGE: &str = "usage: aoc-2021 [DAY | --all] [--record] [--json]
+
+Runs every solution for DAY (default: the latest day) against the input in
+input/2021/dayN.txt, and checks the answers against input/2021/dayN.yaml.
+
+    --all       run the default solution of every day and part, and print
+                a summary table of answers and timings
+    --record    record the answers of the default solutions as verified
+    --json      print one JSON object per solution instead";
+
+enum Mode {
+    Day(u8),
+    All,
+}
+
+struct Args {
+    mode: Mode,
+    record: bool,
+    json: bool,
+}
+
+impl Args {
+    fn parse() -> Result<Self> {
+        let mut mode = None;
+        let mut record = false;
+        let mut json = false;
+        for arg in env::args().skip(1) {
+            match arg.as_str() {
+                "--record" => record = true,
+                "--json" => json = true,
+                "--all" if mode.is_none() => mode = Some(Mode::All),
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                _ => match arg.parse() {
+                    Ok(day) if mode.is_none() => {
+                        if !solutions::SOLUTIONS.iter().any(|s| s.day == day) {
+                            bail!("no solutions for day {}", day);
+                        }
+                        mode = Some(Mode::Day(day))
+                    }
+                    _ => bail!("unexpected argument '{}'\n\n{}", arg, USAGE),
+                },
+            }
+        }
+        let mode = match mode {
+            Some(mode) => mode,
+            None => Mode::Day(solutions::day_parts().last().unwrap().0),
+        };
+        Ok(Self { mode, record, json })
+    }
+}
+
+/// The result of running a solution and checking its answer.
+struct Outcome {
+    solution: &'static Solution,
+    result: Result<(Run, Result<Verdict>)>,
+}
+
+impl Outcome {
+    fn is_ok(&self) -> bool {
+        matches!(
+            self.result,
+            Ok((_, Ok(Verdict::Correct | Verdict::Unknown)))
+        )
+    }
+}
+
+/// Run each solution against its day's input, then check its answer against
+/// the store, or record it if asked to and it's from a default solution.
+fn run(
+    store: &Store,
+    args: &Args,
+    solutions: impl Iterator<Item = &'static Solution>,
+) -> Vec<Outcome> {
+    let mut inputs = HashMap::new();
+    solutions
+        .map(|solution| {
+            let input = inputs
+                .entry(solution.day)
+                .or_insert_with(|| store.input(solution.day));
+            let result = match input {
+                Ok(input) => solution.run(input).map(|run| {
+                    let verdict = if args.record && solution.name.is_none() {
+                        store
+                            .record(solution.day, solution.part, &run.answer)
+                            .map(|_| Verdict::Correct)
+                    } else {
+                        store.check(solution.day, solution.part, &run.answer)
+                    };
+                    (run, verdict)
+                }),
+                Err(e) => Err(anyhow::anyhow!("{}: {:#}", solution, e)),
+            };
+            Outcome { solution, result }
+        })
+        .collect()
+}
+
+/// Print outcomes in the same format as `aoc_main!`.
+fn print_runs(outcomes: &[Outcome]) {
+    for outcome in outcomes {
+        match &outcome.result {
+            Ok((run, verdict)) => {
+                let note = match verdict {
+                    Ok(Verdict::Correct) => "".to_string(),
+                    Ok(Verdict::Unknown) => " (unverified)".to_string(),
+                    Ok(Verdict::Incorrect { expected }) => {
+                        format!(" (MISMATCH: expected {})", expected)
+                    }
+                    Err(e) => format!(" ({:#})", e),
+                };
+                println!(
+                    "{}: {}{}\n\tgenerator: {:?},\n\trunner: {:?}\n",
+                    outcome.solution, run.answer, note, run.generator, run.runner
+                );
+            }
+            Err(e) => eprintln!("{:#}\n", e),
+        }
+    }
+}
+
+/// Print a table of outcomes and the total time taken.
+fn print_table(outcomes: &[Outcome]) {
+    let (mut generator, mut runner) = (Duration::ZERO, Duration::ZERO);
+    let mut rows = vec![[
+        "Day".to_string(),
+        "Part".to_string(),
+        "Answer".to_string(),
+        "Generator".to_string(),
+        "Solver".to_string(),
+        "Status".to_string(),
+    ]];
+    for outcome in outcomes {
+        let (answer, times, status) = match &outcome.result {
+            Ok((run, verdict)) => {
+                generator += run.generator;
+                runner += run.runner;
+                let status = match verdict {
+                    Ok(Verdict::Correct) => "ok".to_string(),
+                    Ok(Verdict::Unknown) => "unverified".to_string(),
+                    Ok(Verdict::Incorrect { expected }) => {
+                        format!("MISMATCH: expected {}", expected)
+                    }
+                    Err(e) => format!("{:#}", e),
+                };
+                (
+                    run.answer.clone(),
+                    Some((run.generator, run.runner)),
+                    status,
+                )
+            }
+            Err(e) => (String::new(), None, format!("{:#}", e)),
+        };
+        let (generator, runner) = match times {
+            Some((g, r)) => (format!("{:.2?}", g), format!("{:.2?}", r)),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        rows.push([
+            outcome.solution.day.to_string(),
+            outcome.solution.part.to_string(),
+            answer,
+            generator,
+            runner,
+            status,
+        ]);
+    }
+
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|i| rows.iter().map(|row| row[i].len()).max().unwrap())
+        .collect();
+    for (i, row) in rows.iter().enumerate() {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(j, (cell, width))| match j {
+                // Left-align the answer and status, right-align everything else.
+                2 | 5 => format!("{:<width$}", cell, width = width),
+                _ => format!("{:>width$}", cell, width = width),
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+        if i == 0 {
+            // Statuses can be long error messages, so only underline the header.
+            println!("{}", "-".repeat(line.trim_end().len()));
+        }
+    }
+    println!(
+        "\nTotal: {:.2?} (generators: {:.2?}, solvers: {:.2?})",
+        generator + runner,
+        generator,
+        runner
+    );
+}
+
+/// A single line of JSON output.
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    #[serde(flatten)]
+    solution: &'a Solution,
+    #[serde(flatten)]
+    run: Option<&'a Run>,
+    #[serde(flatten)]
+    verdict: Option<&'a Verdict>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Print one JSON object per outcome, one per line.
+fn print_json(outcomes: &[Outcome]) {
+    for outcome in outcomes {
+        let (run, verdict, error) = match &outcome.result {
+            Ok((run, Ok(verdict))) => (Some(run), Some(verdict), None),
+            Ok((run, Err(e))) => (Some(run), None, Some(format!("{:#}", e))),
+            Err(e) => (None, None, Some(format!("{:#}", e))),
+        };
+        let line = JsonLine {
+            solution: outcome.solution,
+            run,
+            verdict,
+            error,
+        };
+        println!(
+            "{}",
+            serde_json::to_string(&line).expect("outcomes to serialize")
+        );
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let store = Store::default();
+
+    let outcomes = match args.mode {
+        Mode::Day(day) => run(
+            &store,
+            &args,
+            solutions::SOLUTIONS.iter().filter(|s| s.day == day),
+        ),
+        Mode::All => run(
+            &store,
+            &args,
+            solutions::day_parts()
+                .map(|(day, part)| solutions::variants(day, part).next().unwrap()),
+        ),
+    };
+
+    if args.json {
+        print_json(&outcomes);
+    } else {
+        println!("Advent of code {}", YEAR);
+        match args.mode {
+            Mode::Day(_) => print_runs(&outcomes),
+            Mode::All => print_table(&outcomes),
+        }
+    }
+
+    if outcomes.iter().all(Outcome::is_ok) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
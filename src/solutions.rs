@@ -0,0 +1,278 @@
+//! A registry of every `#[aoc]` solution in the crate, including named variants
+//! such as `#[aoc(day4, part1, Loop)]`.
+//!
+//! `aoc_lib!` only exposes each solution through its own `Factory` trait, which
+//! makes it awkward to iterate over them; this keeps a flat list we can use to
+//! run, compare and benchmark them.
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Result};
+use aoc_runner::{ArcStr, Runner};
+use itertools::Itertools;
+use rand::Rng;
+use serde::{Serialize, Serializer};
+
+use crate::*;
+
+type Generate = fn(ArcStr) -> Result<Box<dyn Runner>, Box<dyn std::error::Error>>;
+
+/// A single registered solution for a day and part.
+#[derive(Clone, Copy, Serialize)]
+pub struct Solution {
+    pub day: u8,
+    pub part: u8,
+    /// The name given to a variant, or `None` for the default solution.
+    pub name: Option<&'static str>,
+    #[serde(skip)]
+    generate: Generate,
+}
+
+impl Solution {
+    const fn new(day: u8, part: u8, name: Option<&'static str>, generate: Generate) -> Self {
+        Self {
+            day,
+            part,
+            name,
+            generate,
+        }
+    }
+
+    /// Run the generator for this solution, returning a runner ready to solve.
+    pub fn runner(&self, input: &str) -> Result<Box<dyn Runner>> {
+        (self.generate)(ArcStr::from(input))
+            .map_err(|e| anyhow!("{}: failed while generating: {}", self, e))
+    }
+
+    /// Generate and solve in one go, timing each step.
+    pub fn run(&self, input: &str) -> Result<Run> {
+        let start_time = Instant::now();
+        let runner = self.runner(input)?;
+        let inter_time = Instant::now();
+        let answer = runner
+            .try_run()
+            .map_err(|e| anyhow!("{}: failed while running: {}", self, e))?
+            .to_string();
+        let final_time = Instant::now();
+        Ok(Run {
+            answer,
+            generator: inter_time - start_time,
+            runner: final_time - inter_time,
+        })
+    }
+
+    /// Generate and solve in one go, returning the displayed answer.
+    pub fn solve(&self, input: &str) -> Result<String> {
+        self.run(input).map(|run| run.answer)
+    }
+}
+
+/// The answer given by a solution, and how long it took to get there.
+///
+/// Timings are serialized as a whole number of nanoseconds.
+#[derive(Clone, Debug, Serialize)]
+pub struct Run {
+    pub answer: String,
+    #[serde(rename = "generator_ns", serialize_with = "serialize_nanos")]
+    pub generator: Duration,
+    #[serde(rename = "runner_ns", serialize_with = "serialize_nanos")]
+    pub runner: Duration,
+}
+
+fn serialize_nanos<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u128(duration.as_nanos())
+}
+
+impl fmt::Debug for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Solution")
+            .field("day", &self.day)
+            .field("part", &self.part)
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Day {} - Part {}", self.day, self.part)?;
+        if let Some(name) = self.name {
+            write!(f, " - {}", name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every solution, ordered by day and part with the default solution first.
+///
+/// Every `#[aoc]` function needs an entry here to be run, compared and
+/// benchmarked; `test_every_aoc_function_is_registered` checks none are
+/// missing.
+pub static SOLUTIONS: &[Solution] = &[
+    Solution::new(1, 1, None, Factory::day1_part1),
+    Solution::new(1, 2, None, Factory::day1_part2),
+    Solution::new(2, 1, None, Factory::day2_part1),
+    Solution::new(2, 2, None, Factory::day2_part2),
+    Solution::new(3, 1, None, Factory::day3_part1),
+    Solution::new(3, 1, Some("Popcount"), Factory::day3_part1_popcount),
+    Solution::new(3, 2, None, Factory::day3_part2),
+    Solution::new(3, 2, Some("Trie"), Factory::day3_part2_trie),
+    Solution::new(4, 1, None, Factory::day4_part1),
+    Solution::new(4, 1, Some("Loop"), Factory::day4_part1_loop),
+    Solution::new(4, 1, Some("Indexed"), Factory::day4_part1_indexed),
+    Solution::new(4, 2, None, Factory::day4_part2),
+    Solution::new(4, 2, Some("Loop"), Factory::day4_part2_loop),
+    Solution::new(4, 2, Some("Indexed"), Factory::day4_part2_indexed),
+    Solution::new(5, 1, None, Factory::day5_part1),
+    Solution::new(5, 1, Some("Naive"), Factory::day5_part1_naive),
+    Solution::new(5, 2, None, Factory::day5_part2),
+    Solution::new(5, 2, Some("Naive"), Factory::day5_part2_naive),
+    Solution::new(6, 1, None, Factory::day6_part1),
+    Solution::new(6, 2, None, Factory::day6_part2),
+    Solution::new(7, 1, None, Factory::day7_part1),
+    Solution::new(7, 2, None, Factory::day7_part2),
+    Solution::new(8, 1, None, Factory::day8_part1),
+    Solution::new(8, 2, None, Factory::day8_part2),
+];
+
+/// The distinct `(day, part)` pairs with at least one solution, in order.
+pub fn day_parts() -> impl Iterator<Item = (u8, u8)> {
+    SOLUTIONS.iter().map(|s| (s.day, s.part)).dedup()
+}
+
+/// All solutions for the given day and part, default first.
+pub fn variants(day: u8, part: u8) -> impl Iterator<Item = &'static Solution> {
+    SOLUTIONS
+        .iter()
+        .filter(move |s| s.day == day && s.part == part)
+}
+
+/// Solve the input with every variant of a day and part, returning the answer
+/// if they all agree.
+pub fn agreed_answer(day: u8, part: u8, input: &str) -> Result<String> {
+    let mut answers = variants(day, part).map(|s| s.solve(input).map(|answer| (s, answer)));
+    let (first, expected) = answers
+        .next()
+        .ok_or_else(|| anyhow!("no solutions for day {} part {}", day, part))??;
+    for answer in answers {
+        let (solution, answer) = answer?;
+        if answer != expected {
+            bail!(
+                "{} gave {} but {} gave {}",
+                solution,
+                answer,
+                first,
+                expected
+            );
+        }
+    }
+    Ok(expected)
+}
+
+/// Generate a random but valid puzzle input for the given day, if that day
+/// has a generator.
+pub fn random_input<R: Rng>(day: u8, rng: &mut R) -> Option<String> {
+    Some(match day {
+        1 => day1::random_input(rng),
+        2 => day2::random_input(rng),
+        3 => day3::random_input(rng),
+        4 => day4::random_input(rng),
+        5 => day5::random_input(rng),
+        6 => day6::random_input(rng),
+        7 => day7::random_input(rng),
+        8 => day8::random_input(rng),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn test_solutions_are_ordered() {
+        assert!(SOLUTIONS
+            .iter()
+            .tuple_windows()
+            .all(|(a, b)| (a.day, a.part, a.name.is_some()) <= (b.day, b.part, b.name.is_some())));
+    }
+
+    #[test]
+    fn test_every_aoc_function_is_registered() {
+        let sources = [
+            include_str!("day1.rs"),
+            include_str!("day2.rs"),
+            include_str!("day3.rs"),
+            include_str!("day4.rs"),
+            include_str!("day5.rs"),
+            include_str!("day6.rs"),
+            include_str!("day7.rs"),
+            include_str!("day8.rs"),
+        ];
+        let declared: Vec<(u8, u8, Option<&str>)> = sources
+            .iter()
+            .flat_map(|source| source.lines())
+            .filter_map(|line| line.trim().strip_prefix("#[aoc(")?.strip_suffix(")]"))
+            .map(|args| {
+                let mut args = args.split(", ");
+                let mut number = |prefix| {
+                    args.next()
+                        .and_then(|arg: &str| arg.strip_prefix(prefix)?.parse().ok())
+                        .unwrap()
+                };
+                (number("day"), number("part"), args.next())
+            })
+            .sorted()
+            .collect();
+        let registered: Vec<(u8, u8, Option<&str>)> = SOLUTIONS
+            .iter()
+            .map(|s| (s.day, s.part, s.name))
+            .sorted()
+            .collect();
+        assert_eq!(registered, declared);
+    }
+
+    #[test]
+    fn test_variants() {
+        assert_eq!(
+            variants(4, 1).map(|s| s.to_string()).collect::<Vec<_>>(),
+            vec![
+                "Day 4 - Part 1",
+                "Day 4 - Part 1 - Loop",
+                "Day 4 - Part 1 - Indexed"
+            ]
+        );
+        let input = "1,2,3,4,5
+
+ 1  2  3  4  5
+ 6  7  8  9 10
+11 12 13 14 15
+16 17 18 19 20
+21 22 23 24 25";
+        assert_eq!(
+            variants(4, 1)
+                .map(|s| s.solve(input))
+                .collect::<Result<Vec<_>>>()
+                .unwrap(),
+            vec!["1550", "1550", "1550"]
+        );
+    }
+
+    #[test]
+    fn test_variants_agree_on_random_inputs() {
+        let mut rng = StdRng::seed_from_u64(2021);
+        for (day, part) in day_parts() {
+            for _ in 0..10 {
+                let input = random_input(day, &mut rng).unwrap();
+                if let Err(e) = agreed_answer(day, part, &input) {
+                    panic!("{}\ninput:\n{}", e, input);
+                }
+            }
+        }
+    }
+}
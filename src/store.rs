@@ -0,0 +1,158 @@
+//! A local store of puzzle inputs and their verified answers.
+//!
+//! Each day has its input in `input/2021/dayN.txt`, as `aoc-runner` expects,
+//! and the answers we know to be correct in `input/2021/dayN.yaml`:
+//!
+//! ```yaml
+//! part1: "4512"
+//! part2: "1924"
+//! ```
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::YEAR;
+
+/// The verified answers for a single day.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Answers {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub part1: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub part2: Option<String>,
+}
+
+impl Answers {
+    pub fn get(&self, part: u8) -> Option<&str> {
+        match part {
+            1 => self.part1.as_deref(),
+            2 => self.part2.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, part: u8, answer: String) {
+        match part {
+            1 => self.part1 = Some(answer),
+            2 => self.part2 = Some(answer),
+            _ => panic!("invalid part {}", part),
+        }
+    }
+}
+
+/// How an answer compares to the one recorded in the store.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "verdict", rename_all = "lowercase")]
+pub enum Verdict {
+    /// The answer matches the recorded one.
+    Correct,
+    /// The answer differs from the recorded one.
+    Incorrect { expected: String },
+    /// There is no recorded answer to compare against.
+    Unknown,
+}
+
+#[derive(Clone, Debug)]
+pub struct Store {
+    root: PathBuf,
+}
+
+impl Default for Store {
+    /// The `input` directory of this crate, wherever it's run from.
+    fn default() -> Self {
+        Self::new(Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("input/{}", YEAR)))
+    }
+}
+
+impl Store {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn input_path(&self, day: u8) -> PathBuf {
+        self.root.join(format!("day{}.txt", day))
+    }
+
+    fn answers_path(&self, day: u8) -> PathBuf {
+        self.root.join(format!("day{}.yaml", day))
+    }
+
+    /// Read the input for a day.
+    pub fn input(&self, day: u8) -> Result<String> {
+        let path = self.input_path(day);
+        fs::read_to_string(&path).with_context(|| format!("reading input {}", path.display()))
+    }
+
+    /// Read the recorded answers for a day, which are empty if none have been
+    /// recorded yet.
+    pub fn answers(&self, day: u8) -> Result<Answers> {
+        let path = self.answers_path(day);
+        if !path.exists() {
+            return Ok(Answers::default());
+        }
+        let f =
+            fs::File::open(&path).with_context(|| format!("opening answers {}", path.display()))?;
+        serde_yaml::from_reader(f).with_context(|| format!("parsing answers {}", path.display()))
+    }
+
+    /// Record a verified answer for a day and part, replacing any existing one.
+    pub fn record(&self, day: u8, part: u8, answer: &str) -> Result<()> {
+        let mut answers = self.answers(day)?;
+        answers.set(part, answer.to_string());
+        let path = self.answers_path(day);
+        fs::create_dir_all(&self.root)
+            .with_context(|| format!("creating {}", self.root.display()))?;
+        let f = fs::File::create(&path)
+            .with_context(|| format!("creating answers {}", path.display()))?;
+        serde_yaml::to_writer(f, &answers)
+            .with_context(|| format!("writing answers {}", path.display()))
+    }
+
+    /// Compare an answer against the recorded one for a day and part.
+    pub fn check(&self, day: u8, part: u8, answer: &str) -> Result<Verdict> {
+        Ok(match self.answers(day)?.get(part) {
+            None => Verdict::Unknown,
+            Some(expected) if expected == answer => Verdict::Correct,
+            Some(expected) => Verdict::Incorrect {
+                expected: expected.to_string(),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_record_and_check() {
+        let root = std::env::temp_dir().join(format!("aoc-2021-store-{}", std::process::id()));
+        let store = Store::new(&root);
+        assert_eq!(store.answers(4).unwrap(), Answers::default());
+        assert_eq!(store.check(4, 1, "4512").unwrap(), Verdict::Unknown);
+
+        store.record(4, 1, "4512").unwrap();
+        store.record(4, 2, "1924").unwrap();
+        assert_eq!(store.check(4, 1, "4512").unwrap(), Verdict::Correct);
+        assert_eq!(
+            store.check(4, 2, "1925").unwrap(),
+            Verdict::Incorrect {
+                expected: "1924".to_string()
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("day4.yaml")).unwrap(),
+            "---\npart1: \"4512\"\npart2: \"1924\"\n"
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+}